@@ -0,0 +1,39 @@
+extern crate tracer;
+use tracer::*;
+use std::time::Instant;
+
+// Traces the same primary rays through the main.rs scene with and without the BVH.
+fn trace(scene: &Scene, rays: &[Ray]) -> f64 {
+    let start = Instant::now();
+    for (i, ray) in rays.iter().enumerate() {
+        let mut sampler = SamplerKind::Random.start(0, i, 0, 1);
+        PathTracer::new().radiance(ray, scene, 50, &mut *sampler);
+    }
+    start.elapsed().as_secs_f64()
+}
+
+fn main() {
+    let mut scene = Scene::new(0.001, f64::INFINITY);
+    scene.fill_random(22, 0);
+
+    let origin = Point::new(13.0, 2.0, 3.0);
+    let (width, height) = (60, 40);
+    let rays: Vec<Ray> = (0..height)
+        .flat_map(|j| (0..width).map(move |i| (i, j)))
+        .map(|(i, j)| {
+            let x = (i as f64 / width as f64 - 0.5) * 5.0;
+            let y = (0.5 - j as f64 / height as f64) * 3.5;
+            Ray::new(origin, Point::new(x, y, x * 0.3) - origin)
+        })
+        .collect();
+
+    scene.use_bvh(false);
+    let linear = trace(&scene, &rays);
+    scene.use_bvh(true);
+    let bvh = trace(&scene, &rays);
+
+    println!("{} rays, scene of side 22", rays.len());
+    println!("linear: {:.3}s", linear);
+    println!("bvh:    {:.3}s", bvh);
+    println!("speedup: {:.1}x", linear / bvh);
+}
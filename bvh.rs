@@ -0,0 +1,209 @@
+use crate::{Ray, Point, Vec3, Hittable, HitRecord, Sampler};
+use std::sync::Arc;
+
+const LEAF_SIZE: usize = 4;
+const SAH_BUCKETS: usize = 12;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Aabb {
+    pub min: Point,
+    pub max: Point,
+}
+
+impl Aabb {
+    pub fn new(min: Point, max: Point) -> Aabb {
+        Aabb {min, max}
+    }
+
+    pub fn surrounding(a: &Aabb, b: &Aabb) -> Aabb {
+        Aabb::new(Vec3::min(&a.min, &b.min), Vec3::max(&a.max, &b.max))
+    }
+
+    // NaN or infinite corners can't be sorted or split, such objects stay outside the tree
+    pub fn is_finite(&self) -> bool {
+        (0..3).all(|axis| self.min[axis].is_finite() && self.max[axis].is_finite())
+    }
+
+    pub fn centroid(&self) -> Point {
+        0.5 * (self.min + self.max)
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let d = self.max - self.min;
+        2.0 * (d.get_x() * d.get_y() + d.get_y() * d.get_z() + d.get_z() * d.get_x())
+    }
+
+    pub fn hit(&self, ray: &Ray, mut min_t: f64, mut max_t: f64) -> bool {
+        for axis in 0..3 {
+            let inv_d = 1.0 / ray.dir[axis];
+            let mut t0 = (self.min[axis] - ray.origin[axis]) * inv_d;
+            let mut t1 = (self.max[axis] - ray.origin[axis]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            min_t = if t0 > min_t { t0 } else { min_t };
+            max_t = if t1 < max_t { t1 } else { max_t };
+            if max_t < min_t {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+enum BvhContents {
+    Leaf(Vec<Arc<dyn Hittable>>),
+    Branch(Box<BvhNode>, Box<BvhNode>, usize),
+}
+
+pub struct BvhNode {
+    bbox: Aabb,
+    contents: BvhContents,
+}
+
+impl BvhNode {
+    // objects must all have a bounding box, unbounded ones are kept outside the tree
+    pub fn new(objs: Vec<Arc<dyn Hittable>>) -> BvhNode {
+        let mut items: Vec<(Aabb, Arc<dyn Hittable>)> = objs
+            .into_iter()
+            .map(|o| (o.bounding_box().expect("object in BVH has no bounding box"), o))
+            .collect();
+        BvhNode::build(&mut items)
+    }
+
+    fn build(items: &mut [(Aabb, Arc<dyn Hittable>)]) -> BvhNode {
+        let bbox = items
+            .iter()
+            .skip(1)
+            .fold(items[0].0, |acc, (b, _)| Aabb::surrounding(&acc, b));
+        if items.len() <= LEAF_SIZE {
+            let objs = items.iter().map(|(_, o)| o.clone()).collect();
+            return BvhNode {bbox, contents: BvhContents::Leaf(objs)};
+        }
+
+        // split along the axis where the centroids are spread the most
+        let (cmin, cmax) = items.iter().fold(
+            (items[0].0.centroid(), items[0].0.centroid()),
+            |(lo, hi), (b, _)| (Vec3::min(&lo, &b.centroid()), Vec3::max(&hi, &b.centroid())),
+        );
+        let extent = cmax - cmin;
+        let axis = if extent[0] > extent[1] && extent[0] > extent[2] {
+            0
+        } else if extent[1] > extent[2] {
+            1
+        } else {
+            2
+        };
+        items.sort_by(|a, b| a.0.centroid()[axis].total_cmp(&b.0.centroid()[axis]));
+
+        let mid = if extent[axis] > 0.0 {
+            BvhNode::sah_split(items, axis, cmin[axis], extent[axis])
+        } else {
+            items.len() / 2
+        };
+        let (left, right) = items.split_at_mut(mid);
+        BvhNode {
+            bbox,
+            contents: BvhContents::Branch(Box::new(BvhNode::build(left)), Box::new(BvhNode::build(right)), axis),
+        }
+    }
+
+    // items are sorted along the axis, so every bucket boundary is a split index
+    fn sah_split(items: &[(Aabb, Arc<dyn Hittable>)], axis: usize, min: f64, extent: f64) -> usize {
+        let bucket_of = |b: &Aabb| {
+            let n = ((b.centroid()[axis] - min) / extent * SAH_BUCKETS as f64) as usize;
+            n.min(SAH_BUCKETS - 1)
+        };
+
+        let mut best = (f64::INFINITY, items.len() / 2);
+        for split in 1..SAH_BUCKETS {
+            let mid = items.iter().take_while(|(b, _)| bucket_of(b) < split).count();
+            if mid == 0 || mid == items.len() {
+                continue;
+            }
+            let area = |part: &[(Aabb, Arc<dyn Hittable>)]| {
+                part.iter().skip(1).fold(part[0].0, |acc, (b, _)| Aabb::surrounding(&acc, b)).surface_area()
+            };
+            let cost = area(&items[..mid]) * mid as f64 + area(&items[mid..]) * (items.len() - mid) as f64;
+            if cost < best.0 {
+                best = (cost, mid);
+            }
+        }
+        best.1
+    }
+
+    // closest hit of the objects before max_t, each one asked by hit_obj with the range's end
+    fn closest<'a>(
+        &'a self,
+        ray: &Ray,
+        min_t: f64,
+        max_t: f64,
+        hit_obj: &mut impl FnMut(&'a dyn Hittable, f64) -> Option<HitRecord<'a>>,
+    ) -> Option<HitRecord<'a>> {
+        if !self.bbox.hit(ray, min_t, max_t) {
+            return None;
+        }
+        match &self.contents {
+            BvhContents::Leaf(objs) => objs.iter().fold(None, |cur_hit: Option<HitRecord>, o| {
+                let cur_max_t = cur_hit.as_ref().map_or(max_t, |hr| hr.t);
+                hit_obj(&**o, cur_max_t).or(cur_hit)
+            }),
+            BvhContents::Branch(left, right, axis) => {
+                // visit the nearer child first so the farther one can often be culled
+                let (first, second) = if ray.dir[*axis] < 0.0 { (right, left) } else { (left, right) };
+                let first_hit = first.closest(ray, min_t, max_t, hit_obj);
+                let cur_max_t = first_hit.as_ref().map_or(max_t, |hr| hr.t);
+                second.closest(ray, min_t, cur_max_t, hit_obj).or(first_hit)
+            },
+        }
+    }
+}
+
+impl Hittable for BvhNode {
+    fn hit(&self, ray: &Ray, min_t: f64, max_t: f64) -> Option<HitRecord<'_>> {
+        self.closest(ray, min_t, max_t, &mut |o, max_t| o.hit(ray, min_t, max_t))
+    }
+
+    fn hit_sampled(&self, ray: &Ray, min_t: f64, max_t: f64, sampler: &mut dyn Sampler) -> Option<HitRecord<'_>> {
+        self.closest(ray, min_t, max_t, &mut |o, max_t| o.hit_sampled(ray, min_t, max_t, sampler))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+}
+
+#[test]
+fn test_aabb_hit() {
+    let bbox = Aabb::new(Point::new(-1.0, -1.0, -1.0), Point::new(1.0, 1.0, 1.0));
+    let ray = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+    assert!(bbox.hit(&ray, 0.0, f64::INFINITY));
+    assert!(!bbox.hit(&ray, 0.0, 3.0));
+    let ray = Ray::new(Point::new(0.0, 2.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+    assert!(!bbox.hit(&ray, 0.0, f64::INFINITY));
+}
+
+#[test]
+fn test_bvh_matches_linear() {
+    use crate::{Scene, SamplerKind};
+    use rand::{Rng, SeedableRng};
+    use rand_pcg::Pcg32;
+
+    let mut with_bvh = Scene::new(0.001, f64::INFINITY);
+    with_bvh.fill_random(12, 5);
+    let mut linear = Scene::new(0.001, f64::INFINITY);
+    linear.fill_random(12, 5);
+    linear.use_bvh(false);
+
+    let mut rng = Pcg32::seed_from_u64(1);
+    let mut sampler = SamplerKind::Random.start(0, 0, 0, 1);
+    for _ in 0..2000 {
+        let origin = Point::new(rng.gen_range(-8.0, 8.0), rng.gen_range(0.1, 3.0), rng.gen_range(-8.0, 8.0));
+        let ray = Ray::new(origin, Vec3::random_unit(&mut rng));
+        match (with_bvh.hit(&ray, &mut *sampler), linear.hit(&ray, &mut *sampler)) {
+            (Some(a), Some(b)) => assert_eq!((a.t, a.p), (b.t, b.p)),
+            (None, None) => {},
+            _ => panic!("BVH and linear hits differ for {:?}", ray.dir),
+        }
+    }
+}
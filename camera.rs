@@ -1,8 +1,29 @@
-extern crate rand;
-
-use crate::{Color, Scene, Ray, Vec3, Point, new_executor_and_spawner};
-use self::rand::Rng;
+use crate::{Scene, Ray, Vec3, Point, Image, Film, Filter, PostProcess, ToneMap, Transfer, Integrator, PathTracer};
+use crate::{Sampler, SamplerKind, sample_disk};
 use std::f64::consts::PI;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+const TILE_SIZE: usize = 32;
+// f-stops are converted to a lens size assuming a 35mm full frame sensor and a
+// scene measured in meters
+const SENSOR_HEIGHT_MM: f64 = 24.0;
+const MM_PER_UNIT: f64 = 1000.0;
+
+// Distance to the plane that is in focus.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Focus {
+    Distance(f64),
+    // the distance to the lookat point
+    Lookat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aperture {
+    Radius(f64),
+    FStop(f64),
+}
 
 pub struct Camera {
     pos: Point,
@@ -11,10 +32,19 @@ pub struct Camera {
     aspect_ratio: f64,
     vertical_fov: f64,
     antialiasing: u32,
-    focal_len: f64,
-    aperture: f64,
+    focus: Focus,
+    aperture: Aperture,
+    // polygonal aperture with this many blades, round below 3
+    blades: u32,
+    shutter: (f64, f64),
     image_width: usize,
     max_recursion: u32,
+    threads: usize,
+    seed: u64,
+    sampler: SamplerKind,
+    filter: Filter,
+    post: PostProcess,
+    integrator: Box<dyn Integrator>,
 }
 
 impl Camera {
@@ -26,10 +56,18 @@ impl Camera {
             aspect_ratio: 16.0 / 9.0,
             vertical_fov: PI / 2.0,
             antialiasing: 10,
-            focal_len: 1.0,
-            aperture: 0.0,
+            focus: Focus::Lookat,
+            aperture: Aperture::Radius(0.0),
+            blades: 0,
+            shutter: (0.0, 0.0),
             image_width: 400,
             max_recursion: 10,
+            threads: thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            seed: 0,
+            sampler: SamplerKind::Random,
+            filter: Filter::default(),
+            post: PostProcess::default(),
+            integrator: Box::new(PathTracer::new()),
         }
     }
 
@@ -48,13 +86,45 @@ impl Camera {
         self
     }
 
-    pub fn focal_length(mut self, fl: f64) -> Self {
-        self.focal_len = fl;
+    pub fn focus_distance(mut self, dist: f64) -> Self {
+        self.focus = Focus::Distance(dist);
+        self
+    }
+
+    pub fn autofocus(mut self) -> Self {
+        self.focus = Focus::Lookat;
+        self
+    }
+
+    pub fn focus(mut self, focus: Focus) -> Self {
+        self.focus = focus;
+        self
+    }
+
+    // diameter of the lens, 0 for a pinhole camera
+    pub fn aperture(mut self, diameter: f64) -> Self {
+        self.aperture = Aperture::Radius(diameter / 2.0);
+        self
+    }
+
+    pub fn f_stop(mut self, f_number: f64) -> Self {
+        self.aperture = Aperture::FStop(f_number);
+        self
+    }
+
+    pub fn blades(mut self, blades: u32) -> Self {
+        self.blades = blades;
         self
     }
 
-    pub fn aperture(mut self, aperture: f64) -> Self {
-        self.aperture = aperture;
+    // rays are spread over the times the shutter is open, moving objects blur. Objects
+    // only move between times 0 and 1, so the shutter has to stay within them.
+    /// # Panics
+    ///
+    /// Unless `0 <= open <= close <= 1`, see `valid_shutter`.
+    pub fn shutter(mut self, open: f64, close: f64) -> Self {
+        assert!(valid_shutter(open, close), "shutter times must be 0 <= open <= close <= 1");
+        self.shutter = (open, close);
         self
     }
 
@@ -68,25 +138,111 @@ impl Camera {
         self
     }
 
-    fn write_out(&self, colors: &Vec<Vec<Color>>, height: usize) {
-        print!("P3\n{} {}\n255\n", self.image_width, height);
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
 
-        for j in 0..height {
-            for i in 0..self.image_width {
-                print!("{}\n", colors[i][j].to_s());
-            }
+    // renders with the same seed are identical whatever the thread count
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn sampler(mut self, sampler: SamplerKind) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn integrator(mut self, integrator: impl Integrator + 'static) -> Self {
+        self.integrator = Box::new(integrator);
+        self
+    }
+
+    // exposure in stops, applied before tone mapping
+    pub fn exposure(mut self, stops: f64) -> Self {
+        self.post.exposure = stops;
+        self
+    }
+
+    pub fn tone_map(mut self, tone_map: ToneMap) -> Self {
+        self.post.tone_map = tone_map;
+        self
+    }
+
+    pub fn transfer(mut self, transfer: Transfer) -> Self {
+        self.post.transfer = transfer;
+        self
+    }
+
+    pub fn dither(mut self, dither: bool) -> Self {
+        self.post.dither = dither;
+        self
+    }
+
+    pub fn post_process(&self) -> PostProcess {
+        self.post
+    }
+
+    pub fn width(&self) -> usize {
+        self.image_width
+    }
+
+    pub fn height(&self) -> usize {
+        // rounded, so a width and height turned into an aspect ratio come back the same
+        ((self.image_width as f64 / self.aspect_ratio).round() as usize).max(1)
+    }
+
+    pub fn focus_dist(&self) -> f64 {
+        match self.focus {
+            Focus::Distance(dist) => dist,
+            Focus::Lookat => (self.lookat - self.pos).len(),
+        }
+    }
+
+    pub fn lens_radius(&self) -> f64 {
+        match self.aperture {
+            Aperture::Radius(radius) => radius,
+            Aperture::FStop(f_number) => {
+                let focal_length = SENSOR_HEIGHT_MM / 2.0 / (self.vertical_fov / 2.0).tan();
+                focal_length / f_number / 2.0 / MM_PER_UNIT
+            },
         }
     }
 
-    fn aperture_offset(&self, right: Vec3, up: Vec3) -> Vec3 {
-        let offset_weight = (self.aperture / 2.0) * Vec3::random_in_unit();
-        offset_weight.get_x() * right + offset_weight.get_y() * up
+    // point on the lens, relative to its center
+    fn aperture_offset(&self, lens_radius: f64, right: Vec3, up: Vec3, sampler: &mut dyn Sampler) -> Vec3 {
+        let (x, y) = if self.blades >= 3 {
+            sample_polygon(self.blades, sampler.next_2d())
+        } else {
+            sample_disk(sampler.next_2d())
+        };
+        lens_radius * (x * right + y * up)
     }
 
-    pub fn render(&self, scene: &Scene) {
+    fn tiles(width: usize, height: usize) -> Vec<Tile> {
+        let mut tiles = Vec::new();
+        for y0 in (0..height).step_by(TILE_SIZE) {
+            for x0 in (0..width).step_by(TILE_SIZE) {
+                tiles.push(Tile {
+                    x0,
+                    y0,
+                    x1: (x0 + TILE_SIZE).min(width),
+                    y1: (y0 + TILE_SIZE).min(height),
+                });
+            }
+        }
+        tiles
+    }
+
+    pub fn render(&self, scene: &Scene) -> Image {
         let image_width = self.image_width;
-        let image_height = (image_width as f64 / self.aspect_ratio) as usize;
-        let mut colors = vec![vec![Color::new(0.0, 0.0, 0.0); image_height]; image_width];
+        let image_height = self.height();
 
         let dir = (self.lookat - self.pos).unit();
         let viewport_height = (self.vertical_fov / 2.0).tan() * 2.0;
@@ -95,46 +251,148 @@ impl Camera {
         let right_vec = Vec3::cross(&dir, &self.up).unit();
         let up_vec = Vec3::cross(&right_vec, &dir).unit();
 
-        let horizontal = self.focal_len * viewport_width * right_vec;
-        let vertical = self.focal_len * viewport_height * up_vec;
+        // the viewport sits on the plane in focus, so rays from anywhere on the lens
+        // through the same viewport point converge there
+        let focus_dist = self.focus_dist();
+        let lens_radius = self.lens_radius();
+        let horizontal = focus_dist * viewport_width * right_vec;
+        let vertical = focus_dist * viewport_height * up_vec;
 
-        let viewport_vec = self.focal_len * dir;
+        let viewport_vec = focus_dist * dir;
         let viewport_upper_left = self.pos - horizontal / 2.0 + vertical / 2.0 + viewport_vec;
 
-        let pos = self.pos;
-        let antialiasing = self.antialiasing;
-        let max_recursion = self.max_recursion;
-        let (executor, spawner) = new_executor_and_spawner();
-
-        for j in 0..image_height {
-            eprint!("\rLine {} of {}", j + 1, image_height);
-            let y = (j as f64) / (image_height as f64 - 1.0);
-            for i in 0..image_width {
-                spawner.spawn(async {
-                    let mut rng = rand::thread_rng();
-
-                    let x = (i as f64) / (image_width as f64 - 1.0);
-                    let start_pos = pos;//  + self.aperture_offset(right_vec, up_vec);
-                    let dir = viewport_upper_left + x * horizontal - y * vertical - pos;
-                    let ray = Ray::new(start_pos, dir);
-                    // colors[i][j] = ray.ray_color(&scene, max_recursion);
-                    for _ in 0..antialiasing {
-                        let dx = rng.gen_range(0.0, 1.0) / (image_width as f64 - 1.0);
-                        let dy = rng.gen_range(0.0, 1.0) / (image_height as f64 - 1.0);
-                        let start_pos = pos;// + aperture_offset(right_vec, up_vec);
-                        let dir = viewport_upper_left + (x + dx) * horizontal - (y + dy) * vertical - start_pos;
-                        let ray = Ray::new(start_pos, dir);
-                        // colors[i][j] += ray.ray_color(&scene, max_recursion);
+        let tiles = Camera::tiles(image_width, image_height);
+        let next_tile = AtomicUsize::new(0);
+        let done_tiles = AtomicUsize::new(0);
+        let films = Mutex::new(Vec::new());
+        let margin = self.filter.radius().ceil() as usize;
+
+        let render_tile = |tile: &Tile| {
+            // samples near the edges also land in the neighbouring tiles' pixels
+            let (x0, y0) = (tile.x0.saturating_sub(margin), tile.y0.saturating_sub(margin));
+            let x1 = tile.x1.saturating_add(margin).min(image_width);
+            let y1 = tile.y1.saturating_add(margin).min(image_height);
+            let mut film = Film::new(x0, y0, x1 - x0, y1 - y0);
+            for j in tile.y0..tile.y1 {
+                for i in tile.x0..tile.x1 {
+                    for sample in 0..self.antialiasing {
+                        // every sample of every pixel gets its own numbers, so the result
+                        // doesn't depend on which thread renders which tile
+                        let mut sampler = self.sampler.start(self.seed, j * image_width + i, sample, self.antialiasing);
+                        let (dx, dy) = sampler.next_2d();
+                        let (film_x, film_y) = (i as f64 + dx, j as f64 + dy);
+                        let x = film_x / (image_width as f64 - 1.0);
+                        let y = film_y / (image_height as f64 - 1.0);
+                        let start_pos = if lens_radius > 0.0 {
+                            self.pos + self.aperture_offset(lens_radius, right_vec, up_vec, &mut *sampler)
+                        } else {
+                            self.pos
+                        };
+                        let dir = viewport_upper_left + x * horizontal - y * vertical - start_pos;
+                        let (open, close) = self.shutter;
+                        let time = if close > open { open + sampler.next_1d() * (close - open) } else { open };
+                        let ray = Ray::with_time(start_pos, dir, time);
+                        let color = self.integrator.radiance(&ray, scene, self.max_recursion, &mut *sampler);
+                        film.add_sample(&self.filter, (film_x, film_y), color);
                     }
-                    // colors[i][j] /= antialiasing as f64 + 1.0;
-                    colors[i][j] = colors[i][j].sqrt()
+                }
+            }
+            film
+        };
+
+        thread::scope(|s| {
+            for _ in 0..self.threads.max(1) {
+                s.spawn(|| loop {
+                    let idx = next_tile.fetch_add(1, Ordering::Relaxed);
+                    let tile = match tiles.get(idx) {
+                        Some(tile) => tile,
+                        None => break,
+                    };
+                    let film = render_tile(tile);
+                    films.lock().unwrap().push((idx, film));
+                    let done = done_tiles.fetch_add(1, Ordering::Relaxed) + 1;
+                    eprint!("\rTile {} of {}", done, tiles.len());
                 });
             }
+        });
+        eprintln!();
+
+        // merged in tile order so the sums don't depend on which thread finished first
+        let mut films = films.into_inner().unwrap();
+        films.sort_by_key(|(idx, _)| *idx);
+        let mut film = Film::new(0, 0, image_width, image_height);
+        for (_, tile_film) in &films {
+            film.merge(tile_film);
         }
-        eprint!("\n");
+        film.to_image()
+    }
+}
+
+// whether a shutter opening at open and closing at close is accepted by Camera::shutter
+pub fn valid_shutter(open: f64, close: f64) -> bool {
+    0.0 <= open && open <= close && close <= 1.0
+}
 
-        executor.run();
+// uniform point in a regular polygon inscribed in the unit circle, one corner pointing up
+fn sample_polygon(sides: u32, (u, v): (f64, f64)) -> (f64, f64) {
+    // pick a triangle between the center and one side, then a point inside it
+    let scaled = u * sides as f64;
+    let side = scaled.floor().min(sides as f64 - 1.0);
+    let u = scaled - side;
+    let corner = |k: f64| {
+        let angle = PI / 2.0 + 2.0 * PI * k / sides as f64;
+        (angle.cos(), angle.sin())
+    };
+    let ((ax, ay), (bx, by)) = (corner(side), corner(side + 1.0));
+    let su = u.sqrt();
+    let (b1, b2) = (su * (1.0 - v), su * v);
+    (b1 * ax + b2 * bx, b1 * ay + b2 * by)
+}
+
+struct Tile {
+    x0: usize,
+    y0: usize,
+    x1: usize,
+    y1: usize,
+}
+
+#[test]
+fn test_render_is_deterministic() {
+    let mut scene = Scene::new(0.001, f64::INFINITY);
+    scene.fill_random(4, 1);
+    let cam = |threads, seed| {
+        Camera::new(Point::new(13.0, 2.0, 3.0), Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0))
+            .image_width(40)
+            .antialiasing(4)
+            .threads(threads)
+            .seed(seed)
+            .filter(Filter::Mitchell(2.0))
+    };
+    let image = cam(1, 5).render(&scene);
+    assert_eq!(cam(3, 5).render(&scene), image);
+    assert_ne!(cam(3, 6).render(&scene), image);
+}
+
+#[test]
+fn test_height_from_aspect_ratio() {
+    for width in 1..200 {
+        for height in 1..200 {
+            let cam = Camera::new(Point::new(0.0, 0.0, 0.0), Point::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0))
+                .image_width(width)
+                .aspect_ratio(width as f64 / height as f64);
+            assert_eq!(cam.height(), height);
+        }
+    }
+}
 
-        self.write_out(&colors, image_height);
+#[test]
+fn test_polygon_aperture() {
+    // samples stay inside the hexagon, whose inner radius is cos(30°)
+    let inner = (PI / 6.0).cos();
+    for i in 0..64 {
+        let (x, y) = sample_polygon(6, (i as f64 / 64.0, ((i * 37) % 64) as f64 / 64.0));
+        let angle = y.atan2(x) - PI / 2.0;
+        let to_side = (angle.rem_euclid(PI / 3.0) - PI / 6.0).cos();
+        assert!((x * x + y * y).sqrt() * to_side <= inner + 1e-12);
     }
 }
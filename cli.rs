@@ -0,0 +1,419 @@
+use tracer::*;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+pub const USAGE: &str = "\
+Usage: ray-tracer [OPTIONS]
+
+Scene:
+  --demo NAME            built-in scene: spheres (default), simple, cornell
+  --scene FILE           load a scene description file instead of a demo
+  --seed N               seed for the random demo scene layout and the render
+
+Image:
+  -w, --width N          image width in pixels
+  -H, --height N         image height in pixels (sets the aspect ratio)
+  -s, --samples N        samples per pixel
+  -d, --max-depth N      maximum number of ray bounces
+  --rr-depth N           bounces before russian roulette may end a path (default: 3)
+  --sampler NAME         random (default), stratified, halton, sobol
+  --filter NAME[:R]      pixel filter with radius R up to 16: box (default), tent,
+                         gaussian, mitchell, lanczos
+  -t, --threads N        render threads (default: all cores)
+  -o, --output PATH      output file, - for stdout (default: out.png)
+  -f, --format FORMAT    output format: png, ppm, hdr, pfm, exr (default: from the output extension)
+
+Post processing (png and ppm only):
+  --exposure STOPS       exposure adjustment in stops
+  --tone-map NAME        clamp (default), reinhard, reinhard:WHITE, aces
+  --gamma G              plain gamma curve instead of the sRGB transfer function
+  --no-dither            quantize without dithering
+
+Camera:
+  --position X,Y,Z       camera position
+  --lookat X,Y,Z         point the camera looks at
+  --up X,Y,Z             camera up direction
+  --fov DEGREES          vertical field of view
+  --aperture A           lens diameter, 0 for a pinhole camera
+  --f-stop N             lens size as an f-number, for a scene in meters
+  --blades N             polygonal aperture with N blades (default: round)
+  --focus-dist D         distance to the plane in focus, or lookat
+  --shutter OPEN,CLOSE   times in [0,1] the shutter is open, moving objects blur (default: 0,0)
+
+  -h, --help             print this help
+";
+
+#[derive(Debug)]
+pub enum SceneSource {
+    Demo(String),
+    File(PathBuf),
+}
+
+#[derive(Debug)]
+pub struct Options {
+    pub scene: SceneSource,
+    pub seed: Option<u64>,
+    pub width: Option<usize>,
+    pub height: Option<usize>,
+    pub samples: Option<u32>,
+    pub max_depth: Option<u32>,
+    pub rr_depth: Option<u32>,
+    pub sampler: Option<SamplerKind>,
+    pub filter: Option<Filter>,
+    pub threads: Option<usize>,
+    pub output: String,
+    pub format: Option<ImageFormat>,
+    pub position: Option<Point>,
+    pub lookat: Option<Point>,
+    pub up: Option<Vec3>,
+    pub fov: Option<f64>,
+    pub aperture: Option<Aperture>,
+    pub blades: Option<u32>,
+    pub focus: Option<Focus>,
+    pub shutter: Option<(f64, f64)>,
+    pub exposure: Option<f64>,
+    pub tone_map: Option<ToneMap>,
+    pub gamma: Option<f64>,
+    pub no_dither: bool,
+}
+
+pub enum Command {
+    Help,
+    Render(Box<Options>),
+}
+
+fn parse_num<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    // nan and inf would only show up as a broken image, like in scene files
+    match value.parse() {
+        Ok(x) if value.parse::<f64>().map_or(true, f64::is_finite) => Ok(x),
+        _ => Err(format!("invalid value '{}' for {}", value, flag)),
+    }
+}
+
+fn parse_vec(flag: &str, value: &str) -> Result<Vec3, String> {
+    let parts = value
+        .split(',')
+        .map(|p| parse_num::<f64>(flag, p.trim()))
+        .collect::<Result<Vec<_>, _>>()?;
+    match parts[..] {
+        [x, y, z] => Ok(Vec3::new(x, y, z)),
+        _ => Err(format!("{} expects X,Y,Z, got '{}'", flag, value)),
+    }
+}
+
+pub fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let mut opts = Options {
+        scene: SceneSource::Demo("spheres".to_string()),
+        seed: None,
+        width: None,
+        height: None,
+        samples: None,
+        max_depth: None,
+        rr_depth: None,
+        sampler: None,
+        filter: None,
+        threads: None,
+        output: "out.png".to_string(),
+        format: None,
+        position: None,
+        lookat: None,
+        up: None,
+        fov: None,
+        aperture: None,
+        blades: None,
+        focus: None,
+        shutter: None,
+        exposure: None,
+        tone_map: None,
+        gamma: None,
+        no_dither: false,
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--no-dither" => {
+                opts.no_dither = true;
+                continue;
+            },
+            _ => {},
+        }
+        // both "--flag value" and "--flag=value" are accepted
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+            _ => (arg, None),
+        };
+        let value = match inline_value.or_else(|| args.next()) {
+            Some(value) => value,
+            None => return Err(format!("missing value for {}", flag)),
+        };
+        let f = flag.as_str();
+        match f {
+            "--demo" => opts.scene = SceneSource::Demo(value),
+            "--scene" => opts.scene = SceneSource::File(PathBuf::from(value)),
+            "--seed" => opts.seed = Some(parse_num(f, &value)?),
+            "-w" | "--width" => opts.width = Some(parse_num(f, &value)?),
+            "-H" | "--height" => opts.height = Some(parse_num(f, &value)?),
+            "-s" | "--samples" => opts.samples = Some(parse_num(f, &value)?),
+            "-d" | "--max-depth" => opts.max_depth = Some(parse_num(f, &value)?),
+            "--rr-depth" => opts.rr_depth = Some(parse_num(f, &value)?),
+            "--sampler" => {
+                let sampler = SamplerKind::from_name(&value).ok_or_else(|| format!("unknown sampler '{}'", value))?;
+                opts.sampler = Some(sampler);
+            },
+            "--filter" => {
+                let filter = Filter::from_name(&value).ok_or_else(|| format!("unknown filter '{}'", value))?;
+                opts.filter = Some(filter);
+            },
+            "-t" | "--threads" => opts.threads = Some(parse_num(f, &value)?),
+            "-o" | "--output" => opts.output = value,
+            "-f" | "--format" => {
+                let format = ImageFormat::from_name(&value).ok_or_else(|| format!("unknown output format '{}'", value))?;
+                opts.format = Some(format);
+            },
+            "--position" => opts.position = Some(parse_vec(f, &value)?),
+            "--lookat" => opts.lookat = Some(parse_vec(f, &value)?),
+            "--up" => opts.up = Some(parse_vec(f, &value)?),
+            "--fov" => opts.fov = Some(parse_num(f, &value)?),
+            "--aperture" => opts.aperture = Some(Aperture::Radius(parse_num::<f64>(f, &value)? / 2.0)),
+            "--f-stop" => opts.aperture = Some(Aperture::FStop(parse_num(f, &value)?)),
+            "--blades" => opts.blades = Some(parse_num(f, &value)?),
+            "--focus-dist" if value == "lookat" => opts.focus = Some(Focus::Lookat),
+            "--focus-dist" => opts.focus = Some(Focus::Distance(parse_num(f, &value)?)),
+            "--shutter" => {
+                let times = value.split(',').map(|t| parse_num(f, t.trim())).collect::<Result<Vec<f64>, _>>()?;
+                match times[..] {
+                    [open, close] if valid_shutter(open, close) => opts.shutter = Some((open, close)),
+                    [_, _] => return Err(format!("{} needs 0 <= OPEN <= CLOSE <= 1, got '{}'", f, value)),
+                    _ => return Err(format!("{} expects OPEN,CLOSE, got '{}'", f, value)),
+                }
+            },
+            "--exposure" => opts.exposure = Some(parse_num(f, &value)?),
+            "--tone-map" => {
+                let tone_map = ToneMap::from_name(&value).ok_or_else(|| format!("unknown tone map '{}'", value))?;
+                opts.tone_map = Some(tone_map);
+            },
+            "--gamma" => {
+                let gamma = parse_num(f, &value)?;
+                if !valid_gamma(gamma) {
+                    return Err(format!("{} must be positive, got '{}'", f, value));
+                }
+                opts.gamma = Some(gamma);
+            },
+            _ => return Err(format!("unknown option '{}'", flag)),
+        }
+    }
+    if opts.width == Some(0) || opts.height == Some(0) {
+        return Err("image size must be positive".to_string());
+    }
+    if opts.samples == Some(0) {
+        return Err("samples per pixel must be positive".to_string());
+    }
+    Ok(Command::Render(Box::new(opts)))
+}
+
+impl Options {
+    pub fn format(&self) -> Result<ImageFormat, String> {
+        match self.format {
+            Some(format) => Ok(format),
+            None if self.output == "-" => Ok(ImageFormat::Ppm),
+            None => ImageFormat::from_path(&self.output)
+                .ok_or_else(|| format!("can't tell the format of '{}', use --format", self.output)),
+        }
+    }
+
+    pub fn load_scene(&self) -> Result<(Scene, CameraDesc), String> {
+        match &self.scene {
+            SceneSource::File(path) => {
+                let desc = SceneDesc::load(path).map_err(|e| e.to_string())?;
+                let base_dir = path.parent().unwrap_or_else(|| std::path::Path::new(""));
+                let (scene, _) = desc.build(base_dir).map_err(|e| e.to_string())?;
+                Ok((scene, desc.camera))
+            },
+            // same layout as a scene file's random with that seed, a new one every run without
+            SceneSource::Demo(name) => demo_scene(name, self.seed.unwrap_or_else(rand::random)),
+        }
+    }
+
+    // command line settings override whatever the scene specified
+    pub fn camera(&self, mut cam: CameraDesc) -> Camera {
+        if let Some(width) = self.width {
+            cam.image_width = Some(width);
+        }
+        if let Some(height) = self.height {
+            let width = cam.image_width.unwrap_or_else(|| cam.build().width());
+            cam.aspect_ratio = Some(width as f64 / height as f64);
+        }
+        cam.antialiasing = self.samples.or(cam.antialiasing);
+        cam.max_recursion = self.max_depth.or(cam.max_recursion);
+        cam.russian_roulette = self.rr_depth.or(cam.russian_roulette);
+        cam.threads = self.threads.or(cam.threads);
+        cam.seed = self.seed.or(cam.seed);
+        cam.sampler = self.sampler.or(cam.sampler);
+        cam.filter = self.filter.or(cam.filter);
+        cam.position = self.position.or(cam.position);
+        cam.lookat = self.lookat.or(cam.lookat);
+        cam.up = self.up.or(cam.up);
+        cam.vertical_fov = self.fov.or(cam.vertical_fov);
+        cam.aperture = self.aperture.or(cam.aperture);
+        cam.blades = self.blades.or(cam.blades);
+        cam.focus = self.focus.or(cam.focus);
+        cam.shutter = self.shutter.or(cam.shutter);
+        cam.exposure = self.exposure.or(cam.exposure);
+        cam.tone_map = self.tone_map.or(cam.tone_map);
+        cam.transfer = self.gamma.map(Transfer::Gamma).or(cam.transfer);
+        if self.no_dither {
+            cam.dither = Some(false);
+        }
+        cam.build()
+    }
+}
+
+fn demo_scene(name: &str, seed: u64) -> Result<(Scene, CameraDesc), String> {
+    let mut scene = Scene::new(0.001, f64::INFINITY);
+    let cam = match name {
+        "spheres" => {
+            scene.fill_random(22, seed);
+            CameraDesc {
+                position: Some(Point::new(13.0, 2.0, 3.0)),
+                lookat: Some(Point::new(0.0, 0.0, 0.0)),
+                up: Some(Vec3::new(0.0, 1.0, 0.0)),
+                aspect_ratio: Some(3.0 / 2.0),
+                vertical_fov: Some(20.0),
+                image_width: Some(1200),
+                antialiasing: Some(500),
+                focus: Some(Focus::Distance(10.0)),
+                aperture: Some(Aperture::Radius(0.05)),
+                max_recursion: Some(50),
+                ..CameraDesc::default()
+            }
+        },
+        "simple" => {
+            let ground = Arc::new(LambertDiffuse::new(Color::new(0.8, 0.8, 0.0)));
+            scene.add(Box::new(Sphere::new(Point::new(0.0, -100.5, -1.0), 100.0, ground)));
+            let center = Arc::new(LambertDiffuse::new(Color::new(0.1, 0.2, 0.5)));
+            scene.add(Box::new(Sphere::new(Point::new(0.0, 0.0, -1.0), 0.5, center)));
+            scene.add(Box::new(Sphere::new(Point::new(-1.0, 0.0, -1.0), 0.5, Arc::new(Dielectric::new(1.5)))));
+            let right = Arc::new(Reflect::new(Color::new(0.8, 0.6, 0.2), 0.0));
+            scene.add(Box::new(Sphere::new(Point::new(1.0, 0.0, -1.0), 0.5, right)));
+            CameraDesc {
+                position: Some(Point::new(-2.0, 2.0, 1.0)),
+                lookat: Some(Point::new(0.0, 0.0, -1.0)),
+                vertical_fov: Some(20.0),
+                focus: Some(Focus::Distance(3.4)),
+                antialiasing: Some(100),
+                max_recursion: Some(50),
+                ..CameraDesc::default()
+            }
+        },
+        "cornell" => {
+            scene.set_background(Background::Color(Color::new(0.0, 0.0, 0.0)));
+            let red: Arc<dyn Material> = Arc::new(LambertDiffuse::new(Color::new(0.65, 0.05, 0.05)));
+            let white: Arc<dyn Material> = Arc::new(LambertDiffuse::new(Color::new(0.73, 0.73, 0.73)));
+            let green: Arc<dyn Material> = Arc::new(LambertDiffuse::new(Color::new(0.12, 0.45, 0.15)));
+            let light: Arc<dyn Material> = Arc::new(DiffuseLight::new(Color::new(15.0, 15.0, 15.0)));
+            let p = Point::new;
+            scene.add(Box::new(YZRect::new((0.0, 555.0), (0.0, 555.0), 555.0, green)));
+            scene.add(Box::new(YZRect::new((0.0, 555.0), (0.0, 555.0), 0.0, red)));
+            scene.add(Box::new(XZRect::new((0.0, 555.0), (0.0, 555.0), 0.0, white.clone())));
+            scene.add(Box::new(XZRect::new((0.0, 555.0), (0.0, 555.0), 555.0, white.clone())));
+            scene.add(Box::new(XYRect::new((0.0, 555.0), (0.0, 555.0), 555.0, white.clone())));
+            scene.add(Box::new(XZRect::new((213.0, 343.0), (227.0, 332.0), 554.0, light)));
+            let tall = Transform::rotate_y(15.0).then(&Transform::translate(Vec3::new(265.0, 0.0, 295.0)));
+            scene.add(Box::new(Instance::new(Arc::new(Cuboid::new(p(0.0, 0.0, 0.0), p(165.0, 330.0, 165.0), white)), tall)));
+            scene.add(Box::new(Sphere::new(p(190.0, 90.0, 190.0), 90.0, Arc::new(Dielectric::new(1.5)))));
+            CameraDesc {
+                position: Some(p(278.0, 278.0, -800.0)),
+                lookat: Some(p(278.0, 278.0, 0.0)),
+                aspect_ratio: Some(1.0),
+                vertical_fov: Some(40.0),
+                image_width: Some(600),
+                antialiasing: Some(200),
+                max_recursion: Some(50),
+                ..CameraDesc::default()
+            }
+        },
+        _ => return Err(format!("unknown demo scene '{}'", name)),
+    };
+    Ok((scene, cam))
+}
+
+#[test]
+fn test_height_and_demo_seed() {
+    let args = |s: &str| match parse_args(s.split_whitespace().map(String::from)) {
+        Ok(Command::Render(opts)) => opts,
+        _ => panic!("expected options for '{}'", s),
+    };
+    let opts = args("-w 600 --height 57 --seed 3");
+    let (scene, cam) = opts.load_scene().unwrap();
+    let cam = opts.camera(cam);
+    assert_eq!((cam.width(), cam.height()), (600, 57));
+
+    // the demo is laid out like a scene file's random spheres with the same seed
+    let mut random = Scene::new(0.001, f64::INFINITY);
+    random.fill_random(22, 3);
+    let mut sampler = SamplerKind::Random.start(0, 0, 0, 1);
+    for x in 0..20 {
+        let ray = Ray::new(Point::new(13.0, 2.0, 3.0), Vec3::new(-13.0, -2.0 + 0.1 * x as f64, -3.0));
+        assert_eq!(scene.hit(&ray, &mut *sampler).map(|hr| hr.t), random.hit(&ray, &mut *sampler).map(|hr| hr.t));
+    }
+}
+
+#[test]
+fn test_parse_args() {
+    let parse = |s: &str| parse_args(s.split_whitespace().map(String::from));
+    for bad in [
+        "--bogus 1",
+        "-w",
+        "-w wide",
+        "-w 0",
+        "--shutter 0",
+        "--shutter 0.5,2",
+        "--shutter 0.5,0.2",
+        "--position 1,2",
+        "--position 1,nan,2",
+        "--lookat inf,0,0",
+        "--fov nan",
+        "--aperture inf",
+        "--focus-dist -inf",
+        "--exposure nan",
+        "--shutter 0,nan",
+        "-s 0",
+        "--sampler dice",
+        "--tone-map reinhard:0",
+        "--gamma 0",
+        "--gamma -2",
+        "--gamma nan",
+        "--filter box:inf",
+        "--filter box:1e30",
+    ].iter() {
+        assert!(parse(bad).is_err(), "'{}' should be rejected", bad);
+    }
+    assert!(matches!(parse("--samples 4 -h"), Ok(Command::Help)));
+
+    let opts = match parse("--scene a.scene -s 8 --fov=30 --shutter 0,0.5 --sampler sobol -o - --no-dither") {
+        Ok(Command::Render(opts)) => opts,
+        _ => panic!("expected options"),
+    };
+    assert!(matches!(&opts.scene, SceneSource::File(path) if path == &PathBuf::from("a.scene")));
+    assert_eq!(opts.format(), Ok(ImageFormat::Ppm));
+
+    assert_eq!((opts.samples, opts.fov, opts.shutter), (Some(8), Some(30.0), Some((0.0, 0.5))));
+
+    // flags override the scene's camera, anything not given is kept
+    let scene_cam = CameraDesc {antialiasing: Some(100), vertical_fov: Some(90.0), image_width: Some(12), ..CameraDesc::default()};
+    let cam = opts.camera(scene_cam.clone());
+    assert_eq!(cam.width(), 12);
+    assert!(!cam.post_process().dither);
+    let expected = CameraDesc {
+        antialiasing: Some(8),
+        vertical_fov: Some(30.0),
+        shutter: Some((0.0, 0.5)),
+        sampler: Some(SamplerKind::Sobol),
+        dither: Some(false),
+        ..scene_cam
+    };
+    let mut scene = Scene::new(0.001, f64::INFINITY);
+    scene.fill_random(2, 1);
+    assert_eq!(cam.render(&scene), expected.build().render(&scene));
+}
@@ -0,0 +1,170 @@
+use crate::{Ray, Vec3, Aabb, Hittable, HitRecord};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CsgOp {
+    Union,
+    Intersection,
+    // the first object with the second carved out of it
+    Difference,
+}
+
+impl CsgOp {
+    pub fn from_name(name: &str) -> Option<CsgOp> {
+        match name.to_ascii_lowercase().as_str() {
+            "union" => Some(CsgOp::Union),
+            "intersection" => Some(CsgOp::Intersection),
+            "difference" => Some(CsgOp::Difference),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CsgOp::Union => "union",
+            CsgOp::Intersection => "intersection",
+            CsgOp::Difference => "difference",
+        }
+    }
+
+    fn inside(&self, in_a: bool, in_b: bool) -> bool {
+        match self {
+            CsgOp::Union => in_a || in_b,
+            CsgOp::Intersection => in_a && in_b,
+            CsgOp::Difference => in_a && !in_b,
+        }
+    }
+}
+
+// Solid combined from two closed objects. The crossings of both along a ray are
+// merged and only those where the ray goes in or out of the result are kept, each
+// with the material of the surface it's on. Surfaces carved out by a difference
+// face the other way, so the ray leaves the solid where it enters the second object.
+pub struct Csg {
+    op: CsgOp,
+    a: Arc<dyn Hittable>,
+    b: Arc<dyn Hittable>,
+}
+
+impl Csg {
+    pub fn new(op: CsgOp, a: Arc<dyn Hittable>, b: Arc<dyn Hittable>) -> Csg {
+        Csg {op, a, b}
+    }
+
+    pub fn union(a: Arc<dyn Hittable>, b: Arc<dyn Hittable>) -> Csg {
+        Csg::new(CsgOp::Union, a, b)
+    }
+
+    pub fn intersection(a: Arc<dyn Hittable>, b: Arc<dyn Hittable>) -> Csg {
+        Csg::new(CsgOp::Intersection, a, b)
+    }
+
+    pub fn difference(a: Arc<dyn Hittable>, b: Arc<dyn Hittable>) -> Csg {
+        Csg::new(CsgOp::Difference, a, b)
+    }
+}
+
+impl Hittable for Csg {
+    fn hit(&self, ray: &Ray, min_t: f64, max_t: f64) -> Option<HitRecord<'_>> {
+        self.crossings(ray, min_t, max_t).into_iter().next()
+    }
+
+    fn crossings(&self, ray: &Ray, min_t: f64, max_t: f64) -> Vec<HitRecord<'_>> {
+        // whether the ray starts inside a child is only known from its first crossing,
+        // so both are followed all the way even for short rays
+        let a = self.a.crossings(ray, min_t, f64::INFINITY);
+        let b = self.b.crossings(ray, min_t, f64::INFINITY);
+        let mut in_a = a.first().is_some_and(|hr| !hr.front_face);
+        let mut in_b = b.first().is_some_and(|hr| !hr.front_face);
+
+        let mut crossings = Vec::new();
+        let (mut a, mut b) = (a.into_iter().peekable(), b.into_iter().peekable());
+        loop {
+            let from_a = match (a.peek(), b.peek()) {
+                (Some(ha), Some(hb)) => ha.t <= hb.t,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break,
+            };
+            let mut hr = if from_a { a.next() } else { b.next() }.unwrap();
+            if hr.t > max_t {
+                break;
+            }
+            let was_inside = self.op.inside(in_a, in_b);
+            if from_a {
+                in_a = hr.front_face;
+            } else {
+                in_b = hr.front_face;
+            }
+            let inside = self.op.inside(in_a, in_b);
+            if inside != was_inside {
+                // the normal already faces the ray, only which side it came from changes
+                hr.front_face = inside;
+                hr.object = self;
+                crossings.push(hr);
+            }
+        }
+        crossings
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let (a, b) = (self.a.bounding_box(), self.b.bounding_box());
+        match self.op {
+            CsgOp::Union => Some(Aabb::surrounding(&a?, &b?)),
+            CsgOp::Intersection => match (a, b) {
+                (Some(a), Some(b)) => {
+                    let min = Vec3::max(&a.min, &b.min);
+                    Some(Aabb::new(min, Vec3::max(&min, &Vec3::min(&a.max, &b.max))))
+                },
+                (a, b) => a.or(b),
+            },
+            CsgOp::Difference => a,
+        }
+    }
+}
+
+#[test]
+fn test_csg() {
+    use crate::{Point, Sphere, Cuboid, Instance, Transform, LambertDiffuse, Color, Material};
+
+    let mat: Arc<dyn Material> = Arc::new(LambertDiffuse::new(Color::new(0.5, 0.5, 0.5)));
+    let cube: Arc<dyn Hittable> = Arc::new(Cuboid::new(Point::new(-1.0, -1.0, -1.0), Point::new(1.0, 1.0, 1.0), mat.clone()));
+    let ball: Arc<dyn Hittable> = Arc::new(Sphere::new(Point::new(0.0, 0.0, 1.0), 0.5, mat.clone()));
+    let along_z = Ray::new(Point::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+    let ts = |obj: &dyn Hittable, ray: &Ray| -> Vec<(f64, bool)> {
+        obj.crossings(ray, 0.0, f64::INFINITY).iter().map(|hr| ((hr.t * 1e6).round() / 1e6, hr.front_face)).collect()
+    };
+
+    // the default crossings of a box find both faces
+    assert_eq!(ts(&*cube, &along_z), vec![(4.0, true), (6.0, false)]);
+
+    // a dent carved into the front face, its surface entered from inside the ball
+    let dented = Csg::difference(cube.clone(), ball.clone());
+    assert_eq!(ts(&dented, &along_z), vec![(4.5, true), (6.0, false)]);
+    let hr = dented.hit(&along_z, 0.0, f64::INFINITY).unwrap();
+    assert!(hr.front_face);
+    assert!((hr.normal - Vec3::new(0.0, 0.0, 1.0)).len() < 1e-9);
+    // rays missing the dent still hit the flat face
+    let side = Ray::new(Point::new(0.8, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+    assert_eq!(ts(&dented, &side), vec![(4.0, true), (6.0, false)]);
+
+    assert_eq!(ts(&Csg::union(cube.clone(), ball.clone()), &along_z), vec![(3.5, true), (6.0, false)]);
+    assert_eq!(ts(&Csg::intersection(cube.clone(), ball.clone()), &along_z), vec![(4.0, true), (4.5, false)]);
+
+    // starting inside, and limited to a short range
+    let inside = Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
+    assert_eq!(ts(&dented, &inside), vec![(0.5, false)]);
+    assert!(dented.hit(&inside, 0.0, 0.4).is_none());
+    assert!(Csg::intersection(cube.clone(), ball.clone()).hit(&inside, 0.0, 0.6).is_some());
+
+    // nested, and moved by an instance
+    let lens = Arc::new(Csg::intersection(
+        Arc::new(Sphere::new(Point::new(0.0, 0.0, -0.6), 1.0, mat.clone())),
+        Arc::new(Sphere::new(Point::new(0.0, 0.0, 0.6), 1.0, mat.clone())),
+    ));
+    let moved = Instance::new(Arc::new(Csg::difference(lens, ball)), Transform::translate(Vec3::new(0.0, 0.0, -1.0)));
+    assert_eq!(ts(&moved, &along_z), vec![(5.6, true), (6.4, false)]);
+    let bbox = moved.bounding_box().unwrap();
+    assert!((bbox.min - Point::new(-1.0, -1.0, -1.4)).len() < 1e-9);
+    assert!((bbox.max - Point::new(1.0, 1.0, -0.6)).len() < 1e-9);
+}
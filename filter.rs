@@ -0,0 +1,116 @@
+use std::f64::consts::PI;
+
+// wider filters only blur, and make every tile reach far into its neighbours
+const MAX_RADIUS: f64 = 16.0;
+
+// Pixel reconstruction filter, each holding its radius in pixels. Samples are
+// weighted by the filter around every pixel center closer than the radius.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    Box(f64),
+    Tent(f64),
+    Gaussian(f64),
+    // Mitchell-Netravali with B = C = 1/3
+    Mitchell(f64),
+    // windowed sinc with as many lobes as the radius
+    Lanczos(f64),
+}
+
+impl Default for Filter {
+    // one pixel wide box, every sample only counts for the pixel it falls in
+    fn default() -> Self {
+        Filter::Box(0.5)
+    }
+}
+
+impl Filter {
+    // "name" with the default radius, or "name:radius" up to 16 pixels
+    pub fn from_name(name: &str) -> Option<Filter> {
+        let name = name.to_ascii_lowercase();
+        let (kind, radius) = match name.split_once(':') {
+            Some((kind, radius)) => (kind, Some(radius.parse().ok().filter(|&r: &f64| r > 0.0 && r <= MAX_RADIUS)?)),
+            None => (name.as_str(), None),
+        };
+        let filter = match kind {
+            "box" => Filter::Box(radius.unwrap_or(0.5)),
+            "tent" => Filter::Tent(radius.unwrap_or(1.0)),
+            "gaussian" => Filter::Gaussian(radius.unwrap_or(1.5)),
+            "mitchell" => Filter::Mitchell(radius.unwrap_or(2.0)),
+            "lanczos" => Filter::Lanczos(radius.unwrap_or(3.0)),
+            _ => return None,
+        };
+        Some(filter)
+    }
+
+    pub fn name(&self) -> String {
+        let (kind, radius) = match *self {
+            Filter::Box(r) => ("box", r),
+            Filter::Tent(r) => ("tent", r),
+            Filter::Gaussian(r) => ("gaussian", r),
+            Filter::Mitchell(r) => ("mitchell", r),
+            Filter::Lanczos(r) => ("lanczos", r),
+        };
+        format!("{}:{}", kind, radius)
+    }
+
+    pub fn radius(&self) -> f64 {
+        match *self {
+            Filter::Box(r) | Filter::Tent(r) | Filter::Gaussian(r) | Filter::Mitchell(r) | Filter::Lanczos(r) => r,
+        }
+    }
+
+    // weight of a sample at offset (dx, dy) from a pixel center
+    pub fn eval(&self, dx: f64, dy: f64) -> f64 {
+        self.eval_1d(dx) * self.eval_1d(dy)
+    }
+
+    fn eval_1d(&self, x: f64) -> f64 {
+        let r = self.radius();
+        let x = x.abs();
+        if x >= r {
+            return 0.0;
+        }
+        match *self {
+            Filter::Box(_) => 1.0,
+            Filter::Tent(_) => 1.0 - x / r,
+            Filter::Gaussian(_) => {
+                // shifted down so it reaches zero at the radius
+                let sigma = r / 3.0;
+                let g = |x: f64| (-x * x / (2.0 * sigma * sigma)).exp();
+                g(x) - g(r)
+            },
+            Filter::Mitchell(_) => {
+                let (b, c) = (1.0 / 3.0, 1.0 / 3.0);
+                let x = 2.0 * x / r;
+                if x < 1.0 {
+                    ((12.0 - 9.0 * b - 6.0 * c) * x.powi(3) + (-18.0 + 12.0 * b + 6.0 * c) * x * x + (6.0 - 2.0 * b)) / 6.0
+                } else {
+                    ((-b - 6.0 * c) * x.powi(3) + (6.0 * b + 30.0 * c) * x * x + (-12.0 * b - 48.0 * c) * x + (8.0 * b + 24.0 * c)) / 6.0
+                }
+            },
+            Filter::Lanczos(_) => sinc(x) * sinc(x / r),
+        }
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x < 1e-8 { 1.0 } else { (PI * x).sin() / (PI * x) }
+}
+
+#[test]
+fn test_filters() {
+    for name in ["box", "tent", "gaussian", "mitchell", "lanczos:2"] {
+        let filter = Filter::from_name(name).unwrap();
+        assert_eq!(Filter::from_name(&filter.name()), Some(filter));
+        let r = filter.radius();
+        assert!(filter.eval(0.0, 0.0) > 0.0, "{}", name);
+        assert_eq!(filter.eval(r, 0.0), 0.0, "{}", name);
+        assert_eq!(filter.eval(0.1, -r - 0.5), 0.0, "{}", name);
+        assert_eq!(filter.eval(0.3, 0.2), filter.eval(-0.3, 0.2), "{}", name);
+    }
+    assert_eq!(Filter::Tent(2.0).eval(1.0, 0.0), 0.5);
+    for bad in ["box:-1", "box:0", "box:inf", "box:nan", "box:1e30", "tent:17"] {
+        assert_eq!(Filter::from_name(bad), None, "{}", bad);
+    }
+    assert_eq!(Filter::from_name("gaussian:16"), Some(Filter::Gaussian(16.0)));
+}
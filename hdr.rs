@@ -0,0 +1,196 @@
+use crate::{Image, Color};
+use std::io::{self, Write};
+
+// Shared-exponent RGBE pixel as used by Radiance .hdr files.
+pub fn to_rgbe(c: Color) -> [u8; 4] {
+    let v = c.get_x().max(c.get_y()).max(c.get_z());
+    if v.is_nan() || v < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+    // v = m * 2^e with m in [0.5, 1)
+    let mut e = v.log2().floor() as i32 + 1;
+    if v / 2f64.powi(e) >= 1.0 {
+        e += 1;
+    }
+    let scale = 256.0 / 2f64.powi(e);
+    let channel = |x: f64| (x.max(0.0) * scale) as u8;
+    [channel(c.get_x()), channel(c.get_y()), channel(c.get_z()), (e + 128).clamp(0, 255) as u8]
+}
+
+impl Image {
+    // flat (not run-length encoded) scanlines, which every reader accepts
+    pub fn write_hdr<W: Write>(&self, mut out: W) -> io::Result<()> {
+        write!(out, "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", self.height(), self.width())?;
+        let data: Vec<u8> = self.pixels().iter().flat_map(|&c| to_rgbe(c)).collect();
+        out.write_all(&data)?;
+        out.flush()
+    }
+
+    // little-endian, rows stored bottom to top
+    pub fn write_pfm<W: Write>(&self, mut out: W) -> io::Result<()> {
+        write!(out, "PF\n{} {}\n-1.0\n", self.width(), self.height())?;
+        let mut data = Vec::with_capacity(self.width() * self.height() * 12);
+        for y in (0..self.height()).rev() {
+            for x in 0..self.width() {
+                let c = self.get(x, y);
+                for v in [c.get_x(), c.get_y(), c.get_z()].iter() {
+                    data.extend_from_slice(&(*v as f32).to_le_bytes());
+                }
+            }
+        }
+        out.write_all(&data)?;
+        out.flush()
+    }
+
+    // single part scanline OpenEXR, uncompressed 32-bit float R, G, B
+    pub fn write_exr<W: Write>(&self, mut out: W) -> io::Result<()> {
+        fn attr(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+            header.extend_from_slice(name.as_bytes());
+            header.push(0);
+            header.extend_from_slice(kind.as_bytes());
+            header.push(0);
+            header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+            header.extend_from_slice(value);
+        }
+
+        let (width, height) = (self.width() as i32, self.height() as i32);
+        // channels have to be listed in alphabetical order
+        let channels = ["B", "G", "R"];
+        let mut chlist = Vec::new();
+        for name in channels.iter() {
+            chlist.extend_from_slice(name.as_bytes());
+            chlist.push(0);
+            chlist.extend_from_slice(&2i32.to_le_bytes()); // FLOAT
+            chlist.extend_from_slice(&[0, 0, 0, 0]); // pLinear + reserved
+            chlist.extend_from_slice(&1i32.to_le_bytes()); // x sampling
+            chlist.extend_from_slice(&1i32.to_le_bytes()); // y sampling
+        }
+        chlist.push(0);
+        let window: Vec<u8> = [0, 0, width - 1, height - 1].iter().flat_map(|v: &i32| v.to_le_bytes()).collect();
+
+        let mut header = vec![0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0];
+        attr(&mut header, "channels", "chlist", &chlist);
+        attr(&mut header, "compression", "compression", &[0]);
+        attr(&mut header, "dataWindow", "box2i", &window);
+        attr(&mut header, "displayWindow", "box2i", &window);
+        attr(&mut header, "lineOrder", "lineOrder", &[0]);
+        attr(&mut header, "pixelAspectRatio", "float", &1f32.to_le_bytes());
+        attr(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+        attr(&mut header, "screenWindowWidth", "float", &1f32.to_le_bytes());
+        header.push(0);
+
+        // one scanline per chunk: y, byte count, then each channel's row
+        let line_size = self.width() * channels.len() * 4;
+        let chunk_size = 8 + line_size;
+        let table_end = header.len() + self.height() * 8;
+        for y in 0..self.height() {
+            header.extend_from_slice(&((table_end + y * chunk_size) as u64).to_le_bytes());
+        }
+        out.write_all(&header)?;
+
+        let mut chunk = Vec::with_capacity(chunk_size);
+        for y in 0..self.height() {
+            chunk.clear();
+            chunk.extend_from_slice(&(y as i32).to_le_bytes());
+            chunk.extend_from_slice(&(line_size as i32).to_le_bytes());
+            for channel in [Color::get_z, Color::get_y, Color::get_x].iter() {
+                for x in 0..self.width() {
+                    chunk.extend_from_slice(&(channel(&self.get(x, y)) as f32).to_le_bytes());
+                }
+            }
+            out.write_all(&chunk)?;
+        }
+        out.flush()
+    }
+}
+
+#[test]
+fn test_rgbe() {
+    assert_eq!(to_rgbe(Color::new(0.0, 0.0, 0.0)), [0, 0, 0, 0]);
+    assert_eq!(to_rgbe(Color::new(1.0, 0.5, 0.25)), [128, 64, 32, 129]);
+    assert_eq!(to_rgbe(Color::new(4.0, 0.0, 0.0)), [128, 0, 0, 131]);
+}
+
+#[cfg(test)]
+fn test_image() -> Image {
+    // channels tell apart the pixel and the channel: x + 2y, plus 10 for g and 20 for b
+    let mut img = Image::new(2, 2);
+    for y in 0..2 {
+        for x in 0..2 {
+            let v = (x + 2 * y) as f64;
+            img.set(x, y, Color::new(v, v + 10.0, v + 20.0));
+        }
+    }
+    img
+}
+
+#[cfg(test)]
+fn f32s(bytes: &[u8]) -> Vec<f32> {
+    bytes.chunks(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect()
+}
+
+#[test]
+fn test_pfm() {
+    let mut out = Vec::new();
+    test_image().write_pfm(&mut out).unwrap();
+    let header = b"PF\n2 2\n-1.0\n";
+    assert_eq!(&out[..header.len()], &header[..]);
+    assert_eq!(out.len(), header.len() + 2 * 2 * 12);
+    // the bottom row comes first
+    assert_eq!(f32s(&out[header.len()..]), vec![2.0, 12.0, 22.0, 3.0, 13.0, 23.0, 0.0, 10.0, 20.0, 1.0, 11.0, 21.0]);
+}
+
+#[test]
+fn test_exr() {
+    use std::convert::TryInto;
+
+    let mut out = Vec::new();
+    test_image().write_exr(&mut out).unwrap();
+    let i32_at = |pos: usize| i32::from_le_bytes([out[pos], out[pos + 1], out[pos + 2], out[pos + 3]]);
+    assert_eq!(&out[..8], &[0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+
+    // attributes are name, type, size and value until an empty name
+    let mut attrs = Vec::new();
+    let mut pos = 8;
+    let string = |pos: &mut usize| {
+        let end = *pos + out[*pos..].iter().position(|&b| b == 0).unwrap();
+        let s = String::from_utf8(out[*pos..end].to_vec()).unwrap();
+        *pos = end + 1;
+        s
+    };
+    loop {
+        let name = string(&mut pos);
+        if name.is_empty() {
+            break;
+        }
+        let kind = string(&mut pos);
+        let size = i32_at(pos) as usize;
+        attrs.push((name, kind, out[pos + 4..pos + 4 + size].to_vec()));
+        pos += 4 + size;
+    }
+    let names: Vec<&str> = attrs.iter().map(|(name, _, _)| name.as_str()).collect();
+    assert_eq!(names, vec![
+        "channels", "compression", "dataWindow", "displayWindow", "lineOrder",
+        "pixelAspectRatio", "screenWindowCenter", "screenWindowWidth",
+    ]);
+    let attr = |name: &str| &attrs.iter().find(|(n, _, _)| n == name).unwrap().2;
+    let chlist = attr("channels");
+    assert_eq!(chlist.len(), 3 * 18 + 1);
+    assert_eq!((&chlist[..2], &chlist[18..20], &chlist[36..38]), (&b"B\0"[..], &b"G\0"[..], &b"R\0"[..]));
+    assert_eq!(i32::from_le_bytes([chlist[2], chlist[3], chlist[4], chlist[5]]), 2);
+    assert_eq!(attr("compression"), &vec![0]);
+    let window: Vec<u8> = [0i32, 0, 1, 1].iter().flat_map(|v| v.to_le_bytes()).collect();
+    assert_eq!((attr("dataWindow"), attr("displayWindow")), (&window, &window));
+
+    // the offset table points at each scanline's chunk, right after the table
+    let offset = |y: usize| u64::from_le_bytes(out[pos + 8 * y..pos + 8 * y + 8].try_into().unwrap()) as usize;
+    let line_size = 2 * 3 * 4;
+    assert_eq!((offset(0), offset(1)), (pos + 16, pos + 16 + 8 + line_size));
+    assert_eq!(out.len(), offset(1) + 8 + line_size);
+    for y in 0..2 {
+        let chunk = offset(y);
+        assert_eq!((i32_at(chunk), i32_at(chunk + 4)), (y as i32, line_size as i32));
+        let v = 2.0 * y as f32;
+        assert_eq!(f32s(&out[chunk + 8..chunk + 8 + line_size]), vec![v + 20.0, v + 21.0, v + 10.0, v + 11.0, v, v + 1.0]);
+    }
+}
@@ -0,0 +1,108 @@
+use crate::{Color, Filter};
+
+// Row-major framebuffer, (0, 0) is the top left pixel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize) -> Image {
+        Image {width, height, pixels: vec![Color::new(0.0, 0.0, 0.0); width * height]}
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: Color) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [Color] {
+        &mut self.pixels
+    }
+}
+
+// Weighted sums of the samples splatted into a window of an image, so tiles can be
+// rendered separately and merged afterwards.
+#[derive(Debug, Clone)]
+pub struct Film {
+    x0: usize,
+    y0: usize,
+    width: usize,
+    height: usize,
+    sums: Vec<Color>,
+    weights: Vec<f64>,
+}
+
+impl Film {
+    // window of width x height pixels starting at (x0, y0)
+    pub fn new(x0: usize, y0: usize, width: usize, height: usize) -> Film {
+        Film {
+            x0,
+            y0,
+            width,
+            height,
+            sums: vec![Color::new(0.0, 0.0, 0.0); width * height],
+            weights: vec![0.0; width * height],
+        }
+    }
+
+    // adds a sample at (x, y) in image coordinates, pixel (i, j) covering [i, i + 1) x [j, j + 1),
+    // to every pixel center in the window within the filter radius
+    pub fn add_sample(&mut self, filter: &Filter, (x, y): (f64, f64), color: Color) {
+        let r = filter.radius();
+        let span = |c: f64, lo: usize, len: usize| {
+            let first = (c - r - 0.5).ceil().max(lo as f64) as usize;
+            let last = ((c + r - 0.5).floor() + 1.0).clamp(lo as f64, (lo + len) as f64) as usize;
+            first..last.max(first)
+        };
+        for j in span(y, self.y0, self.height) {
+            for i in span(x, self.x0, self.width) {
+                let weight = filter.eval(x - (i as f64 + 0.5), y - (j as f64 + 0.5));
+                if weight != 0.0 {
+                    let idx = (j - self.y0) * self.width + (i - self.x0);
+                    self.sums[idx] += weight * color;
+                    self.weights[idx] += weight;
+                }
+            }
+        }
+    }
+
+    // adds another film's sums, where its window overlaps this one
+    pub fn merge(&mut self, other: &Film) {
+        for j in other.y0.max(self.y0)..(other.y0 + other.height).min(self.y0 + self.height) {
+            for i in other.x0.max(self.x0)..(other.x0 + other.width).min(self.x0 + self.width) {
+                let src = (j - other.y0) * other.width + (i - other.x0);
+                let dst = (j - self.y0) * self.width + (i - self.x0);
+                self.sums[dst] += other.sums[src];
+                self.weights[dst] += other.weights[src];
+            }
+        }
+    }
+
+    pub fn to_image(&self) -> Image {
+        let mut image = Image::new(self.width, self.height);
+        for (pixel, (&sum, &weight)) in image.pixels_mut().iter_mut().zip(self.sums.iter().zip(&self.weights)) {
+            if weight != 0.0 {
+                *pixel = sum / weight;
+            }
+        }
+        image
+    }
+}
@@ -0,0 +1,187 @@
+use crate::{Ray, Vec3, Color, Scene, Bounce, Sampler};
+
+const ROULETTE_MIN_BOUNCES: u32 = 3;
+// even bright paths get some chance of ending instead of all running to the max depth
+const ROULETTE_MAX_SURVIVAL: f64 = 0.95;
+
+// Turns a camera ray into the radiance arriving along it, drawing every random
+// number from the sampler so that renders are reproducible.
+pub trait Integrator: Send + Sync {
+    fn radiance(&self, ray: &Ray, scene: &Scene, max_depth: u32, sampler: &mut dyn Sampler) -> Color;
+}
+
+// Unidirectional path tracer with next event estimation, area lights are
+// combined with bsdf sampling by multiple importance sampling.
+#[derive(Debug, Clone, Copy)]
+pub struct PathTracer {
+    roulette_bounces: u32,
+}
+
+impl PathTracer {
+    pub fn new() -> PathTracer {
+        PathTracer {roulette_bounces: ROULETTE_MIN_BOUNCES}
+    }
+
+    // Paths get randomly terminated with a probability based on their throughput after
+    // this many bounces, survivors are weighted up to stay unbiased.
+    // Anything at or above the max depth turns roulette off.
+    pub fn russian_roulette(mut self, min_bounces: u32) -> Self {
+        self.roulette_bounces = min_bounces;
+        self
+    }
+}
+
+impl Default for PathTracer {
+    fn default() -> Self {
+        PathTracer::new()
+    }
+}
+
+impl Integrator for PathTracer {
+    fn radiance(&self, ray: &Ray, scene: &Scene, max_depth: u32, sampler: &mut dyn Sampler) -> Color {
+        let mut color = Color::new(0.0, 0.0, 0.0);
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut ray = Ray::with_time(ray.origin, ray.dir, ray.time);
+        // how the current ray left the previous surface, None for camera and specular rays
+        let mut bounce: Option<Bounce> = None;
+
+        for depth in 0..max_depth {
+            let hr = match scene.hit(&ray, sampler) {
+                Some(hr) => hr,
+                None => {
+                    color += throughput * scene.bg_color(&ray.dir);
+                    break;
+                },
+            };
+            let material = hr.material;
+
+            let mut emitted = material.emitted(&hr);
+            if material.is_light() {
+                emitted *= scene.emission_weight(hr.object, &ray, bounce.as_ref());
+            }
+            color += throughput * emitted;
+
+            let scatter = match material.scatter(&hr, sampler) {
+                Some(scatter) => scatter,
+                None => break,
+            };
+            bounce = if scatter.specular {
+                None
+            } else {
+                let eval = |dir: Vec3| material.eval(&hr, dir);
+                color += throughput * scene.direct_light(hr.p, ray.time, eval, sampler);
+                // without a pdf there's no way to weight light samples, so only bsdf sampling finds area lights
+                let pdf = material.pdf(&hr, scatter.dir);
+                if pdf > 0.0 {
+                    color += throughput * scene.sample_area_light(hr.p, ray.time, eval, |dir| material.pdf(&hr, dir), sampler);
+                    Some(Bounce {origin: hr.p, pdf})
+                } else {
+                    None
+                }
+            };
+
+            throughput *= scatter.attenuation;
+            ray = Ray::with_time(hr.p, scatter.dir, ray.time);
+
+            if depth >= self.roulette_bounces {
+                let survival = throughput.get_x().max(throughput.get_y()).max(throughput.get_z()).min(ROULETTE_MAX_SURVIVAL);
+                if survival <= 0.0 || sampler.next_1d() >= survival {
+                    break;
+                }
+                throughput /= survival;
+            }
+        }
+        color
+    }
+}
+
+#[test]
+fn test_russian_roulette_unbiased() {
+    use crate::{Point, Sphere, LambertDiffuse, Light, SamplerKind};
+    use std::sync::Arc;
+
+    // inside a diffuse sphere lit by a point light at its center every bounce adds
+    // albedo / pi of the light, so the radiance is albedo / (pi * (1 - albedo))
+    let mut scene = Scene::new(0.001, f64::INFINITY);
+    let albedo = 0.5;
+    scene.add(Box::new(Sphere::new(Point::new(0.0, 0.0, 0.0), 1.0, Arc::new(LambertDiffuse::new(Color::new(albedo, albedo, albedo))))));
+    scene.add_light(Light::Point {position: Point::new(0.0, 0.0, 0.0), intensity: Color::new(1.0, 1.0, 1.0)});
+    let expected = albedo / (std::f64::consts::PI * (1.0 - albedo));
+
+    let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+    let n = 20000;
+    let mean = |tracer: PathTracer| {
+        (0..n).map(|i| {
+            let mut sampler = SamplerKind::Random.start(1, 0, i, n);
+            tracer.radiance(&ray, &scene, 50, &mut *sampler).get_x()
+        }).sum::<f64>() / n as f64
+    };
+    let without = mean(PathTracer::new().russian_roulette(50));
+    assert!((without / expected - 1.0).abs() < 1e-6, "{} vs {}", without, expected);
+    let with = mean(PathTracer::new().russian_roulette(0));
+    assert!((with / expected - 1.0).abs() < 0.02, "{} vs {}", with, expected);
+}
+
+#[test]
+fn test_area_light_mis() {
+    use crate::{Point, Sphere, Plane, TriangleMesh, LambertDiffuse, DiffuseLight, Background, SamplerKind};
+    use std::sync::Arc;
+
+    // a diffuse floor right below a spherical light of radius r at height h reflects
+    // albedo * emission * r^2 / h^2, found by light and bsdf sampling together
+    let mut scene = Scene::new(0.001, f64::INFINITY);
+    scene.set_background(Background::Color(Color::new(0.0, 0.0, 0.0)));
+    let albedo = 0.6;
+    let floor = Arc::new(LambertDiffuse::new(Color::new(albedo, albedo, albedo)));
+    scene.add(Box::new(Plane::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), floor.clone())));
+    let (r, h) = (1.0, 3.0);
+    scene.add(Box::new(Sphere::new(Point::new(0.0, h, 0.0), r, Arc::new(DiffuseLight::new(Color::new(4.0, 4.0, 4.0))))));
+    let expected = albedo * 4.0 * r * r / (h * h);
+
+    let ray = Ray::new(Point::new(-1.0, 1.0, 0.0), Vec3::new(1.0, -1.0, 0.0));
+    let n = 20000;
+    let tracer = PathTracer::new();
+    let mean = (0..n).map(|i| {
+        let mut sampler = SamplerKind::Random.start(1, 0, i, n);
+        tracer.radiance(&ray, &scene, 2, &mut *sampler).get_x()
+    }).sum::<f64>() / n as f64;
+    assert!((mean / expected - 1.0).abs() < 0.02, "{} vs {}", mean, expected);
+
+    // under a square mesh light of half size a at height a it's albedo * emission times
+    // the form factor, also with vertex normals that don't match the faces
+    let a = 1.0;
+    let vertices = vec![Point::new(-a, a, -a), Point::new(a, a, -a), Point::new(a, a, a), Point::new(-a, a, a)];
+    let tilted = vec![Vec3::new(0.7, -1.0, 0.7), Vec3::new(-0.7, -1.0, 0.7), Vec3::new(-0.7, -1.0, -0.7), Vec3::new(0.7, -1.0, -0.7)];
+    let s = 1.0 / 2f64.sqrt();
+    let expected = albedo * 4.0 * 4.0 / std::f64::consts::PI * s * s.atan();
+    let ray = Ray::new(Point::new(-3.0, 0.5, 0.0), Vec3::new(3.0, -0.5, 0.0));
+    for normals in [None, Some(tilted)].iter() {
+        let mut scene = Scene::new(0.001, f64::INFINITY);
+        scene.set_background(Background::Color(Color::new(0.0, 0.0, 0.0)));
+        scene.add(Box::new(Plane::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), floor.clone())));
+        let light = Arc::new(DiffuseLight::new(Color::new(4.0, 4.0, 4.0)));
+        scene.add(Box::new(TriangleMesh::new(vertices.clone(), normals.clone(), None, vec![[0, 1, 2], [0, 2, 3]], light)));
+        let mean = (0..n).map(|i| {
+            let mut sampler = SamplerKind::Random.start(2, 0, i, n);
+            tracer.radiance(&ray, &scene, 2, &mut *sampler).get_x()
+        }).sum::<f64>() / n as f64;
+        assert!((mean / expected - 1.0).abs() < 0.02, "{} vs {} with normals {:?}", mean, expected, normals.is_some());
+    }
+}
+
+#[test]
+fn test_deep_paths() {
+    use crate::{Point, Plane, Reflect, SamplerKind};
+    use std::sync::Arc;
+
+    // bouncing between two mirrors far deeper than a recursive tracer's stack would allow
+    let mut scene = Scene::new(0.001, f64::INFINITY);
+    let mirror = Arc::new(Reflect::new(Color::new(1.0, 1.0, 1.0), 0.0));
+    scene.add(Box::new(Plane::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), mirror.clone())));
+    scene.add(Box::new(Plane::new(Point::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0), mirror)));
+    let ray = Ray::new(Point::new(0.0, 0.5, 0.0), Vec3::new(0.0, 1.0, 0.0));
+    let mut sampler = SamplerKind::Random.start(1, 0, 0, 1);
+    let depth = 100_000;
+    let color = PathTracer::new().russian_roulette(depth).radiance(&ray, &scene, depth, &mut *sampler);
+    assert_eq!(color, Color::new(0.0, 0.0, 0.0));
+}
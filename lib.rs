@@ -2,10 +2,46 @@ mod vec;
 mod ray;
 mod scene;
 mod camera;
-mod async_worker;
+mod bvh;
+mod triangle;
+mod quad;
+mod shapes;
+mod obj;
+mod scene_file;
+mod image;
+mod output;
+mod hdr;
+mod tonemap;
+mod light;
+mod material;
+mod integrator;
+mod sampler;
+mod filter;
+mod texture;
+mod transform;
+mod csg;
+mod medium;
 
 pub use vec::*;
 pub use ray::*;
 pub use scene::*;
 pub use camera::*;
-pub use async_worker::*;
+pub use bvh::*;
+pub use triangle::*;
+pub use quad::*;
+pub use shapes::*;
+pub use obj::*;
+pub use scene_file::*;
+pub use image::*;
+pub use output::*;
+pub use hdr::*;
+pub use tonemap::*;
+pub use light::*;
+pub use material::*;
+pub use integrator::*;
+pub use sampler::*;
+pub use filter::*;
+pub use texture::*;
+pub use transform::*;
+pub use csg::*;
+pub use medium::*;
@@ -0,0 +1,76 @@
+use crate::{Point, Vec3, Color};
+
+// Lights that can't be hit by rays and are only found by sampling them from a surface.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Light {
+    Point {position: Point, intensity: Color},
+    // full intensity inside the inner cone, fading out to zero at the outer one (angles in degrees)
+    Spot {position: Point, direction: Vec3, intensity: Color, inner_angle: f64, outer_angle: f64},
+    // direction is the one the light travels in
+    Directional {direction: Vec3, irradiance: Color},
+}
+
+pub struct LightSample {
+    pub dir: Vec3,
+    pub dist: f64,
+    pub irradiance: Color,
+}
+
+impl Light {
+    // incoming light at p: unit direction towards the light, distance to it and the
+    // irradiance on a surface facing the light
+    pub fn sample(&self, p: Point) -> Option<LightSample> {
+        match *self {
+            Light::Point {position, intensity} => {
+                let to_light = position - p;
+                let dist = to_light.len();
+                Some(LightSample {dir: to_light / dist, dist, irradiance: intensity / (dist * dist)})
+            },
+            Light::Spot {position, direction, intensity, inner_angle, outer_angle} => {
+                let to_light = position - p;
+                let dist = to_light.len();
+                let dir = to_light / dist;
+                let cos = -Vec3::dot(&dir, &direction.unit());
+                let (cos_inner, cos_outer) = (inner_angle.to_radians().cos(), outer_angle.to_radians().cos());
+                if cos <= cos_outer {
+                    return None;
+                }
+                let falloff = if cos >= cos_inner {
+                    1.0
+                } else {
+                    let x = (cos - cos_outer) / (cos_inner - cos_outer);
+                    x * x * (3.0 - 2.0 * x)
+                };
+                Some(LightSample {dir, dist, irradiance: falloff * intensity / (dist * dist)})
+            },
+            Light::Directional {direction, irradiance} => {
+                Some(LightSample {dir: -1.0 * direction.unit(), dist: f64::INFINITY, irradiance})
+            },
+        }
+    }
+}
+
+#[test]
+fn test_light_falloff() {
+    let white = Color::new(1.0, 1.0, 1.0);
+    let point = Light::Point {position: Point::new(0.0, 4.0, 0.0), intensity: white};
+    let sample = point.sample(Point::new(0.0, 0.0, 0.0)).unwrap();
+    assert_eq!((sample.dir, sample.dist, sample.irradiance), (Vec3::new(0.0, 1.0, 0.0), 4.0, white / 16.0));
+
+    // pointing straight down with a 20 to 30 degree edge
+    let spot = Light::Spot {
+        position: Point::new(0.0, 1.0, 0.0),
+        direction: Vec3::new(0.0, -2.0, 0.0),
+        intensity: white,
+        inner_angle: 20.0,
+        outer_angle: 30.0,
+    };
+    let at_angle = |degrees: f64| spot.sample(Point::new(degrees.to_radians().tan(), 0.0, 0.0)).map(|s| s.dist * s.dist * s.irradiance);
+    assert_eq!(at_angle(0.0), Some(white));
+    assert!((at_angle(19.0).unwrap() - white).len() < 1e-12);
+    let edge = at_angle(25.0).unwrap().get_x();
+    assert!(edge > 0.0 && edge < 1.0);
+    assert!(at_angle(30.5).is_none());
+    assert!(at_angle(80.0).is_none());
+    assert!(spot.sample(Point::new(0.0, 2.0, 0.0)).is_none());
+}
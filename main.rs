@@ -1,24 +1,43 @@
 extern crate tracer;
-use tracer::*;
-use std::f64::INFINITY;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::process;
+
+mod cli;
+use cli::Command;
 
 fn main() {
-    // hi();
-    // return;
+    let opts = match cli::parse_args(std::env::args().skip(1)) {
+        Ok(Command::Render(opts)) => opts,
+        Ok(Command::Help) => {
+            print!("{}", cli::USAGE);
+            return;
+        },
+        Err(err) => {
+            eprintln!("error: {}\nTry --help for usage.", err);
+            process::exit(2);
+        },
+    };
 
-    let mut scene = Scene::new(0.001, INFINITY);
-    scene.fill_random(22);
+    let format = opts.format().unwrap_or_else(|err| {
+        eprintln!("error: {}", err);
+        process::exit(2);
+    });
+    let (scene, cam_desc) = opts.load_scene().unwrap_or_else(|err| {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    });
+    let cam = opts.camera(cam_desc);
+    let image = cam.render(&scene);
 
-    let origin = Point::new(13.0, 2.0, 3.0);
-    let lookat = Vec3::new(0.0, 0.0, 0.0);
-    let up = Vec3::new(0.0, 1.0, 0.0);
-    let cam = Camera::new(origin, lookat, up)
-        .aspect_ratio(3.0 / 2.0)
-        .vertical_fov(20.0)
-        .image_width(1200)
-        .antialiasing(500)
-        .focal_length(10.0)
-        .aperture(0.1)
-        .max_recursion(50);
-    cam.render(&scene);
+    let post = cam.post_process();
+    let result = if opts.output == "-" {
+        image.write(format, &post, io::stdout().lock())
+    } else {
+        File::create(&opts.output).and_then(|f| image.write(format, &post, BufWriter::new(f)))
+    };
+    if let Err(err) = result {
+        eprintln!("error: could not write {}: {}", opts.output, err);
+        process::exit(1);
+    }
 }
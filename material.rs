@@ -0,0 +1,239 @@
+use crate::{Vec3, Color, HitRecord, Sampler, Texture, SolidColor, sample_sphere, sample_ball};
+use std::f64::consts::PI;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+pub struct Scatter {
+    pub dir: Vec3,
+    // bsdf * cos / pdf of the sampled direction
+    pub attenuation: Color,
+    // mirrors and glass can't be lit by sampling lights
+    pub specular: bool,
+}
+
+pub trait Material: Send + Sync + Debug {
+    // None means the ray is absorbed
+    fn scatter(&self, hr: &HitRecord, sampler: &mut dyn Sampler) -> Option<Scatter>;
+
+    fn emitted(&self, _hr: &HitRecord) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    // area lights get sampled directly at diffuse surfaces
+    fn is_light(&self) -> bool {
+        false
+    }
+
+    // bsdf * cos for light arriving from dir
+    fn eval(&self, _hr: &HitRecord, _dir: Vec3) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    // density of scatter picking dir, 0 when it can't be computed
+    fn pdf(&self, _hr: &HitRecord, _dir: Vec3) -> f64 {
+        0.0
+    }
+}
+
+// Unlit, shows the surface normal as a color.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Normal;
+
+impl Material for Normal {
+    fn scatter(&self, _hr: &HitRecord, _sampler: &mut dyn Sampler) -> Option<Scatter> {
+        None
+    }
+
+    fn emitted(&self, hr: &HitRecord) -> Color {
+        0.5 * (hr.normal + 1.0)
+    }
+}
+
+// Unlit constant color.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlatColor(pub Color);
+
+impl Material for FlatColor {
+    fn scatter(&self, _hr: &HitRecord, _sampler: &mut dyn Sampler) -> Option<Scatter> {
+        None
+    }
+
+    fn emitted(&self, _hr: &HitRecord) -> Color {
+        self.0
+    }
+}
+
+// Grey diffuse bouncing uniformly inside the unit ball around the normal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Diffuse;
+
+impl Material for Diffuse {
+    fn scatter(&self, hr: &HitRecord, sampler: &mut dyn Sampler) -> Option<Scatter> {
+        let dir = hr.normal + sample_ball(sampler);
+        Some(Scatter {dir, attenuation: Color::new(0.5, 0.5, 0.5), specular: false})
+    }
+
+    fn eval(&self, hr: &HitRecord, dir: Vec3) -> Color {
+        let cos = Vec3::dot(&dir.unit(), &hr.normal).max(0.0);
+        0.5 * cos / PI * Color::new(1.0, 1.0, 1.0)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LambertDiffuse {
+    pub albedo: Arc<dyn Texture>,
+}
+
+impl LambertDiffuse {
+    pub fn new(albedo: Color) -> LambertDiffuse {
+        LambertDiffuse::textured(Arc::new(SolidColor(albedo)))
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>) -> LambertDiffuse {
+        LambertDiffuse {albedo}
+    }
+}
+
+impl Material for LambertDiffuse {
+    // cosine weighted, so the attenuation is just the albedo
+    fn scatter(&self, hr: &HitRecord, sampler: &mut dyn Sampler) -> Option<Scatter> {
+        let mut dir = hr.normal + sample_sphere(sampler.next_2d());
+        if dir.len_sq() < 1e-12 {
+            dir = hr.normal;
+        }
+        Some(Scatter {dir, attenuation: self.albedo.value(hr.u, hr.v, hr.p), specular: false})
+    }
+
+    fn eval(&self, hr: &HitRecord, dir: Vec3) -> Color {
+        Vec3::dot(&dir.unit(), &hr.normal).max(0.0) / PI * self.albedo.value(hr.u, hr.v, hr.p)
+    }
+
+    fn pdf(&self, hr: &HitRecord, dir: Vec3) -> f64 {
+        Vec3::dot(&dir.unit(), &hr.normal).max(0.0) / PI
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Reflect {
+    pub albedo: Arc<dyn Texture>,
+    pub fuzz: f64,
+}
+
+impl Reflect {
+    pub fn new(albedo: Color, fuzz: f64) -> Reflect {
+        Reflect::textured(Arc::new(SolidColor(albedo)), fuzz)
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>, fuzz: f64) -> Reflect {
+        Reflect {albedo, fuzz}
+    }
+}
+
+impl Material for Reflect {
+    fn scatter(&self, hr: &HitRecord, sampler: &mut dyn Sampler) -> Option<Scatter> {
+        Some(Scatter {dir: hr.reflect(self.fuzz, sampler), attenuation: self.albedo.value(hr.u, hr.v, hr.p), specular: true})
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dielectric {
+    pub refract_idx: f64,
+}
+
+impl Dielectric {
+    pub fn new(refract_idx: f64) -> Dielectric {
+        Dielectric {refract_idx}
+    }
+}
+
+impl Material for Dielectric {
+    fn scatter(&self, hr: &HitRecord, sampler: &mut dyn Sampler) -> Option<Scatter> {
+        let dir = hr.refract_by(self.refract_idx, sampler);
+        Some(Scatter {dir, attenuation: Color::new(1.0, 1.0, 1.0), specular: true})
+    }
+}
+
+// Phase function of a participating medium, scattering by the angle to the ray's
+// direction. g in (-1, 1) goes from backward through isotropic at 0 to forward
+// scattering.
+#[derive(Debug, Clone)]
+pub struct HenyeyGreenstein {
+    pub albedo: Arc<dyn Texture>,
+    pub g: f64,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(albedo: Color, g: f64) -> HenyeyGreenstein {
+        HenyeyGreenstein::textured(Arc::new(SolidColor(albedo)), g)
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>, g: f64) -> HenyeyGreenstein {
+        HenyeyGreenstein {albedo, g}
+    }
+
+    pub fn isotropic(albedo: Color) -> HenyeyGreenstein {
+        HenyeyGreenstein::new(albedo, 0.0)
+    }
+
+    // density over directions with cos to the ray's direction
+    fn phase(&self, cos: f64) -> f64 {
+        let g = self.g;
+        let denom = 1.0 + g * g - 2.0 * g * cos;
+        (1.0 - g * g) / (4.0 * PI * denom * denom.sqrt())
+    }
+}
+
+impl Material for HenyeyGreenstein {
+    // sampled exactly, so the attenuation is just the albedo
+    fn scatter(&self, hr: &HitRecord, sampler: &mut dyn Sampler) -> Option<Scatter> {
+        let (su, sv) = sampler.next_2d();
+        let g = self.g;
+        let cos = if g.abs() < 1e-3 {
+            1.0 - 2.0 * su
+        } else {
+            let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * su);
+            ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+        };
+        let sin = (1.0 - cos * cos).max(0.0).sqrt();
+        let phi = 2.0 * PI * sv;
+        let w = hr.ray_dir.unit();
+        let (u, v) = w.basis();
+        let dir = sin * phi.cos() * u + sin * phi.sin() * v + cos * w;
+        Some(Scatter {dir, attenuation: self.albedo.value(hr.u, hr.v, hr.p), specular: false})
+    }
+
+    // media have no surface to take a cosine with
+    fn eval(&self, hr: &HitRecord, dir: Vec3) -> Color {
+        self.pdf(hr, dir) * self.albedo.value(hr.u, hr.v, hr.p)
+    }
+
+    fn pdf(&self, hr: &HitRecord, dir: Vec3) -> f64 {
+        self.phase(Vec3::dot(&hr.ray_dir.unit(), &dir.unit()))
+    }
+}
+
+// Area light, emits the same radiance on both sides and reflects nothing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiffuseLight {
+    pub emit: Color,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> DiffuseLight {
+        DiffuseLight {emit}
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _hr: &HitRecord, _sampler: &mut dyn Sampler) -> Option<Scatter> {
+        None
+    }
+
+    fn emitted(&self, _hr: &HitRecord) -> Color {
+        self.emit
+    }
+
+    fn is_light(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,140 @@
+use crate::{Ray, Aabb, Hittable, HitRecord, Material, Sampler};
+use std::sync::Arc;
+
+// Fog or smoke filling a closed boundary, scattering rays at random points inside
+// it with the phase function material. The chance of getting a distance d through
+// is exp(-density * d), so shadow rays get dimmed by it too. Where a ray stops takes
+// a random number, so only hit_sampled finds the medium.
+pub struct ConstantMedium {
+    boundary: Arc<dyn Hittable>,
+    density: f64,
+    phase: Arc<dyn Material>,
+}
+
+impl ConstantMedium {
+    /// # Panics
+    ///
+    /// If `density` isn't finite and positive.
+    pub fn new(boundary: Arc<dyn Hittable>, density: f64, phase: Arc<dyn Material>) -> ConstantMedium {
+        assert!(density.is_finite() && density > 0.0, "medium density must be positive");
+        ConstantMedium {boundary, density, phase}
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, _ray: &Ray, _min_t: f64, _max_t: f64) -> Option<HitRecord<'_>> {
+        None
+    }
+
+    fn hit_sampled(&self, ray: &Ray, min_t: f64, max_t: f64, sampler: &mut dyn Sampler) -> Option<HitRecord<'_>> {
+        let mut left = -(1.0 - sampler.next_1d()).ln() / self.density;
+
+        // like csg, whether the ray starts inside comes from the first crossing
+        let crossings = self.boundary.crossings(ray, min_t, f64::INFINITY);
+        let mut inside = crossings.first().is_some_and(|hr| !hr.front_face);
+        let speed = ray.dir.len();
+        let mut start = min_t;
+        for (end, enters) in crossings.iter().map(|hr| (hr.t, hr.front_face)).chain(Some((f64::INFINITY, true))) {
+            let end = end.min(max_t);
+            if inside {
+                let dist = (end - start) * speed;
+                if left < dist {
+                    let t = start + left / speed;
+                    // there's no surface, the normal only has to be something valid
+                    return Some(HitRecord::new(ray, t, -1.0 * ray.dir.unit(), (0.0, 0.0), &*self.phase, self));
+                }
+                left -= dist;
+            }
+            if end >= max_t {
+                break;
+            }
+            start = end;
+            inside = enters;
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.boundary.bounding_box()
+    }
+}
+
+#[test]
+fn test_constant_medium() {
+    use crate::{Point, Vec3, Color, Sphere, Cuboid, Csg, Instance, Transform, Scene, LambertDiffuse, HenyeyGreenstein, SamplerKind};
+
+    let white: Arc<dyn Material> = Arc::new(LambertDiffuse::new(Color::new(1.0, 1.0, 1.0)));
+    let phase: Arc<dyn Material> = Arc::new(HenyeyGreenstein::isotropic(Color::new(1.0, 1.0, 1.0)));
+
+    // the fraction of rays getting through matches exp(-density * length), also through
+    // a boundary with two separate stretches inside
+    let slab = Arc::new(Cuboid::new(Point::new(-1.0, -1.0, -1.0), Point::new(1.0, 1.0, 1.0), white.clone()));
+    let hole = Arc::new(Sphere::new(Point::new(0.0, 0.0, 0.0), 0.5, white.clone()));
+    let holed = Arc::new(Csg::difference(slab.clone(), hole));
+    for (boundary, length) in [(slab as Arc<dyn Hittable>, 2.0), (holed, 1.0)].iter() {
+        let medium = ConstantMedium::new(boundary.clone(), 0.7, phase.clone());
+        let n = 20000;
+        let through = (0..n)
+            .filter(|i| {
+                let x = 0.04 * (*i as f64 + 0.5) / n as f64 - 0.02;
+                let ray = Ray::new(Point::new(x, x, 5.0), Vec3::new(0.0, 0.0, -2.0));
+                let mut sampler = SamplerKind::Random.start(3, *i, 0, 1);
+                match medium.hit_sampled(&ray, 0.0, f64::INFINITY, &mut *sampler) {
+                    Some(hr) => {
+                        assert!(hr.p.get_z().abs() <= 1.0 && (*length > 1.0 || hr.p.len() >= 0.5 - 1e-9));
+                        false
+                    },
+                    None => true,
+                }
+            })
+            .count();
+        let expected = f64::exp(-0.7 * length);
+        assert!((through as f64 / n as f64 - expected).abs() < 0.02, "{} vs {}", through as f64 / n as f64, expected);
+    }
+
+    // where a ray scatters follows the sampler, and without one it goes straight through
+    let ball: Arc<dyn Hittable> = Arc::new(Sphere::new(Point::new(0.0, 0.0, 0.0), 1.0, white));
+    let medium = ConstantMedium::new(ball.clone(), 0.5, phase.clone());
+    let ray = Ray::new(Point::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+    let scatter_t = |seed| medium.hit_sampled(&ray, 0.0, f64::INFINITY, &mut *SamplerKind::Random.start(seed, 0, 0, 1)).map(|hr| hr.t);
+    assert_eq!(scatter_t(1), scatter_t(1));
+    assert!((1..10).any(|seed| scatter_t(seed) != scatter_t(0)));
+    assert!(medium.hit(&ray, 0.0, f64::INFINITY).is_none());
+
+    // found through instances and the scene's bvh
+    let mut scene = Scene::new(0.001, f64::INFINITY);
+    let dense = Arc::new(ConstantMedium::new(ball.clone(), 1e6, phase.clone()));
+    scene.add(Box::new(Instance::new(dense, Transform::translate(Vec3::new(0.0, 0.0, -2.0)))));
+    let mut sampler = SamplerKind::Random.start(0, 0, 0, 1);
+    let hr = scene.hit(&ray, &mut *sampler).unwrap();
+    assert!((hr.t - 6.0).abs() < 1e-4);
+
+    // starting inside, and cut short
+    let medium = ConstantMedium::new(ball, 1e6, phase);
+    let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+    assert!(medium.hit_sampled(&ray, 0.0, f64::INFINITY, &mut *sampler).unwrap().t < 1e-4);
+    assert!(medium.hit_sampled(&ray, 2.0, f64::INFINITY, &mut *sampler).is_none());
+
+    // the phase function is sampled by its pdf and has the mean cosine g
+    let forward = HenyeyGreenstein::new(Color::new(1.0, 1.0, 1.0), 0.6);
+    let hr = medium.hit_sampled(&ray, 0.0, f64::INFINITY, &mut *sampler).unwrap();
+    let (mut mean_cos, mut inv_pdf_sum) = (0.0, 0.0);
+    let n = 20000;
+    for i in 0..n {
+        let mut sampler = SamplerKind::Sobol.start(1, 0, i, n);
+        let dir = forward.scatter(&hr, &mut *sampler).unwrap().dir;
+        mean_cos += dir.get_y() / n as f64;
+        inv_pdf_sum += 1.0 / forward.pdf(&hr, dir) / n as f64;
+    }
+    assert!((mean_cos - 0.6).abs() < 0.01, "{}", mean_cos);
+    assert!((inv_pdf_sum / (4.0 * std::f64::consts::PI) - 1.0).abs() < 0.05, "{}", inv_pdf_sum);
+}
+
+#[test]
+#[should_panic(expected = "medium density must be positive")]
+fn test_zero_density() {
+    use crate::{Point, Color, Sphere, HenyeyGreenstein};
+
+    let phase: Arc<dyn Material> = Arc::new(HenyeyGreenstein::isotropic(Color::new(1.0, 1.0, 1.0)));
+    ConstantMedium::new(Arc::new(Sphere::new(Point::new(0.0, 0.0, 0.0), 1.0, phase.clone())), 0.0, phase);
+}
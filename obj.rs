@@ -0,0 +1,396 @@
+use crate::{Point, Vec3, Color, Scene, TriangleMesh, Material, LambertDiffuse, Reflect, Dielectric, DiffuseLight};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(PathBuf, io::Error),
+    Parse {file: String, line: usize, message: String},
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            ObjError::Parse {file, line, message} => write!(f, "{}:{}: {}", file, line, message),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+pub struct ObjMesh {
+    pub name: String,
+    pub material: Option<String>,
+    pub vertices: Vec<Point>,
+    pub normals: Option<Vec<Vec3>>,
+    pub uvs: Option<Vec<(f64, f64)>>,
+    pub faces: Vec<[usize; 3]>,
+    material_line: usize,
+}
+
+pub struct ObjModel {
+    pub meshes: Vec<ObjMesh>,
+    pub materials: HashMap<String, Arc<dyn Material>>,
+    mtllibs: Vec<(String, usize)>,
+}
+
+// (position, uv, normal) indices into the file-wide lists
+type FaceVertex = (usize, Option<usize>, Option<usize>);
+
+struct MeshBuilder {
+    mesh: ObjMesh,
+    // file-wide face vertex -> index in this mesh
+    remap: HashMap<FaceVertex, usize>,
+    missing_normals: bool,
+    missing_uvs: bool,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f64, f64)>,
+}
+
+impl MeshBuilder {
+    fn new(name: &str, material: Option<String>, material_line: usize) -> MeshBuilder {
+        MeshBuilder {
+            mesh: ObjMesh {
+                name: name.to_string(),
+                material,
+                vertices: Vec::new(),
+                normals: None,
+                uvs: None,
+                faces: Vec::new(),
+                material_line,
+            },
+            remap: HashMap::new(),
+            missing_normals: false,
+            missing_uvs: false,
+            normals: Vec::new(),
+            uvs: Vec::new(),
+        }
+    }
+
+    fn vertex(&mut self, fv: FaceVertex, positions: &[Point], uvs: &[(f64, f64)], normals: &[Vec3]) -> usize {
+        if let Some(&idx) = self.remap.get(&fv) {
+            return idx;
+        }
+        let (v, vt, vn) = fv;
+        let idx = self.mesh.vertices.len();
+        self.mesh.vertices.push(positions[v]);
+        match vt {
+            Some(vt) => self.uvs.push(uvs[vt]),
+            None => {
+                self.missing_uvs = true;
+                self.uvs.push((0.0, 0.0));
+            },
+        }
+        match vn {
+            Some(vn) => self.normals.push(normals[vn]),
+            None => {
+                self.missing_normals = true;
+                self.normals.push(Vec3::new(0.0, 0.0, 0.0));
+            },
+        }
+        self.remap.insert(fv, idx);
+        idx
+    }
+
+    fn finish(mut self) -> ObjMesh {
+        // per-vertex attributes are only kept when every vertex of the mesh has them
+        if !self.missing_normals {
+            self.mesh.normals = Some(self.normals);
+        }
+        if !self.missing_uvs {
+            self.mesh.uvs = Some(self.uvs);
+        }
+        self.mesh
+    }
+}
+
+struct LineParser<'a> {
+    file: &'a str,
+    line: usize,
+}
+
+impl<'a> LineParser<'a> {
+    fn error(&self, message: String) -> ObjError {
+        ObjError::Parse {file: self.file.to_string(), line: self.line, message}
+    }
+
+    fn floats(&self, args: &[&str], min: usize, max: usize) -> Result<Vec<f64>, ObjError> {
+        if args.len() < min || args.len() > max {
+            return Err(self.error(format!("expected {} to {} numbers, got {}", min, max, args.len())));
+        }
+        args.iter()
+            .map(|a| match a.parse::<f64>() {
+                Ok(x) if x.is_finite() => Ok(x),
+                _ => Err(self.error(format!("invalid number '{}'", a))),
+            })
+            .collect()
+    }
+
+    fn color(&self, args: &[&str]) -> Result<Color, ObjError> {
+        let c = self.floats(args, 1, 3)?;
+        Ok(if c.len() == 3 { Color::new(c[0], c[1], c[2]) } else { Color::new(c[0], c[0], c[0]) })
+    }
+
+    fn index(&self, s: &str, count: usize, what: &str) -> Result<usize, ObjError> {
+        let idx: i64 = s.parse().map_err(|_| self.error(format!("invalid {} index '{}'", what, s)))?;
+        // OBJ indices are 1-based, negative ones count back from the last element
+        let resolved = if idx > 0 { idx - 1 } else { count as i64 + idx };
+        if idx == 0 || resolved < 0 || resolved >= count as i64 {
+            return Err(self.error(format!("{} index {} out of range ({} defined)", what, idx, count)));
+        }
+        Ok(resolved as usize)
+    }
+
+    fn face_vertex(&self, s: &str, counts: (usize, usize, usize)) -> Result<FaceVertex, ObjError> {
+        let mut parts = s.split('/');
+        let v = self.index(parts.next().unwrap_or(""), counts.0, "vertex")?;
+        let vt = match parts.next() {
+            Some("") | None => None,
+            Some(vt) => Some(self.index(vt, counts.1, "texture coordinate")?),
+        };
+        let vn = match parts.next() {
+            Some("") | None => None,
+            Some(vn) => Some(self.index(vn, counts.2, "normal")?),
+        };
+        if parts.next().is_some() {
+            return Err(self.error(format!("invalid face vertex '{}'", s)));
+        }
+        Ok((v, vt, vn))
+    }
+}
+
+fn lines(src: &str) -> impl Iterator<Item = (usize, &str, Vec<&str>)> {
+    src.lines().enumerate().filter_map(|(i, line)| {
+        let line = line.split('#').next().unwrap_or("");
+        let mut words = line.split_whitespace();
+        words.next().map(|keyword| (i + 1, keyword, words.collect()))
+    })
+}
+
+pub fn parse_mtl(src: &str, file: &str) -> Result<HashMap<String, Arc<dyn Material>>, ObjError> {
+    struct Mtl {
+        kd: Color,
+        ks: Color,
+        ke: Color,
+        ns: f64,
+        ni: f64,
+        d: f64,
+        illum: u32,
+    }
+
+    impl Mtl {
+        fn material(&self) -> Arc<dyn Material> {
+            let is_black = |c: Color| c.get_x() <= 0.0 && c.get_y() <= 0.0 && c.get_z() <= 0.0;
+            if !is_black(self.ke) {
+                Arc::new(DiffuseLight::new(self.ke))
+            } else if self.d < 1.0 || [4, 6, 7, 9].contains(&self.illum) {
+                Arc::new(Dielectric::new(self.ni))
+            } else if !is_black(self.ks) && (self.illum == 3 || is_black(self.kd)) {
+                // map the Phong exponent to a fuzz radius, higher exponents are sharper
+                let fuzz = (2.0 / (self.ns + 2.0)).sqrt().min(1.0);
+                Arc::new(Reflect::new(self.ks, fuzz))
+            } else {
+                Arc::new(LambertDiffuse::new(self.kd))
+            }
+        }
+    }
+
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, Mtl)> = None;
+    for (line, keyword, args) in lines(src) {
+        let p = LineParser {file, line};
+        if keyword == "newmtl" {
+            if let Some((name, mtl)) = current.take() {
+                materials.insert(name, mtl.material());
+            }
+            if args.is_empty() {
+                return Err(p.error("newmtl without a name".to_string()));
+            }
+            let black = Color::new(0.0, 0.0, 0.0);
+            let mtl = Mtl {kd: Color::new(0.8, 0.8, 0.8), ks: black, ke: black, ns: 0.0, ni: 1.5, d: 1.0, illum: 2};
+            current = Some((args.join(" "), mtl));
+            continue;
+        }
+        let mtl = match current.as_mut() {
+            Some((_, mtl)) => mtl,
+            None => return Err(p.error(format!("'{}' before any newmtl", keyword))),
+        };
+        match keyword {
+            "Kd" => mtl.kd = p.color(&args)?,
+            "Ks" => mtl.ks = p.color(&args)?,
+            "Ke" => mtl.ke = p.color(&args)?,
+            "Ns" => mtl.ns = p.floats(&args, 1, 1)?[0],
+            "Ni" => mtl.ni = p.floats(&args, 1, 1)?[0],
+            "d" => mtl.d = p.floats(&args, 1, 1)?[0],
+            "Tr" => mtl.d = 1.0 - p.floats(&args, 1, 1)?[0],
+            "illum" => {
+                mtl.illum = args
+                    .first()
+                    .and_then(|a| a.parse().ok())
+                    .ok_or_else(|| p.error("illum needs an integer model".to_string()))?
+            },
+            // everything else (ambient, textures, ...) has no equivalent here
+            _ => {},
+        }
+    }
+    if let Some((name, mtl)) = current {
+        materials.insert(name, mtl.material());
+    }
+    Ok(materials)
+}
+
+impl ObjModel {
+    pub fn parse(src: &str, file: &str) -> Result<ObjModel, ObjError> {
+        let mut positions = Vec::new();
+        let mut uvs = Vec::new();
+        let mut normals = Vec::new();
+        let mut mtllibs = Vec::new();
+        let mut meshes = Vec::new();
+
+        let mut group = String::from("default");
+        let mut material: Option<(String, usize)> = None;
+        let mut builder: Option<MeshBuilder> = None;
+
+        for (line, keyword, args) in lines(src) {
+            let p = LineParser {file, line};
+            match keyword {
+                "v" => {
+                    // x y z with an optional w, or some exporters' vertex colors
+                    let c = p.floats(&args, 3, 7)?;
+                    positions.push(Point::new(c[0], c[1], c[2]));
+                },
+                "vt" => {
+                    let c = p.floats(&args, 1, 3)?;
+                    uvs.push((c[0], c.get(1).copied().unwrap_or(0.0)));
+                },
+                "vn" => {
+                    let c = p.floats(&args, 3, 3)?;
+                    normals.push(Vec3::new(c[0], c[1], c[2]));
+                },
+                "f" => {
+                    if args.len() < 3 {
+                        return Err(p.error(format!("face needs at least 3 vertices, got {}", args.len())));
+                    }
+                    let counts = (positions.len(), uvs.len(), normals.len());
+                    let fvs = args
+                        .iter()
+                        .map(|a| p.face_vertex(a, counts))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let b = builder.get_or_insert_with(|| {
+                        let (name, line) = material.clone().map_or((None, 0), |(m, l)| (Some(m), l));
+                        MeshBuilder::new(&group, name, line)
+                    });
+                    let idx: Vec<usize> = fvs.into_iter().map(|fv| b.vertex(fv, &positions, &uvs, &normals)).collect();
+                    // fan triangulation, fine for the convex polygons exporters write
+                    for i in 1..idx.len() - 1 {
+                        b.mesh.faces.push([idx[0], idx[i], idx[i + 1]]);
+                    }
+                },
+                "g" | "o" => {
+                    meshes.extend(builder.take().map(MeshBuilder::finish));
+                    group = if args.is_empty() { "default".to_string() } else { args.join(" ") };
+                },
+                "usemtl" => {
+                    if args.is_empty() {
+                        return Err(p.error("usemtl without a name".to_string()));
+                    }
+                    meshes.extend(builder.take().map(MeshBuilder::finish));
+                    material = Some((args.join(" "), line));
+                },
+                "mtllib" => mtllibs.extend(args.iter().map(|a| (a.to_string(), line))),
+                // smoothing groups, lines, points, free-form surfaces, merging groups, ... aren't rendered
+                _ => {},
+            }
+        }
+        meshes.extend(builder.take().map(MeshBuilder::finish));
+        meshes.retain(|m| !m.faces.is_empty());
+
+        Ok(ObjModel {meshes, materials: HashMap::new(), mtllibs})
+    }
+
+    // loads the .obj and every .mtl it references, relative to the .obj's directory
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<ObjModel, ObjError> {
+        let path = path.as_ref();
+        let read = |p: &Path| fs::read_to_string(p).map_err(|e| ObjError::Io(p.to_path_buf(), e));
+        let mut model = ObjModel::parse(&read(path)?, &path.display().to_string())?;
+
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        for (lib, _) in &model.mtllibs {
+            let mtl_path = dir.join(lib);
+            let mtl = parse_mtl(&read(&mtl_path)?, &mtl_path.display().to_string())?;
+            model.materials.extend(mtl);
+        }
+        for mesh in &model.meshes {
+            if let Some(name) = &mesh.material {
+                if !model.materials.contains_key(name) {
+                    return Err(ObjError::Parse {
+                        file: path.display().to_string(),
+                        line: mesh.material_line,
+                        message: format!("unknown material '{}'", name),
+                    });
+                }
+            }
+        }
+        Ok(model)
+    }
+
+    pub fn add_to(self, scene: &mut Scene) {
+        for mesh in self.into_meshes() {
+            scene.add(Box::new(mesh));
+        }
+    }
+
+    // one mesh per group and material, each with its own BVH
+    pub fn into_meshes(self) -> Vec<TriangleMesh> {
+        let materials = self.materials;
+        let default: Arc<dyn Material> = Arc::new(LambertDiffuse::new(Color::new(0.8, 0.8, 0.8)));
+        self.meshes
+            .into_iter()
+            .map(|mesh| {
+                let material = mesh
+                    .material
+                    .and_then(|name| materials.get(&name).cloned())
+                    .unwrap_or_else(|| default.clone());
+                TriangleMesh::new(mesh.vertices, mesh.normals, mesh.uvs, mesh.faces, material)
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_parse_obj() {
+    let src = "mtllib box.mtl\n\
+               v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+               vn 0 0 1\n\
+               g quad\nusemtl red\n\
+               f 1//1 2//1 3//1 4//1\n\
+               g tri\nf -4 -3 -2\n";
+    let model = ObjModel::parse(src, "test.obj").unwrap();
+    assert_eq!(model.meshes.len(), 2);
+    assert_eq!(model.meshes[0].name, "quad");
+    assert_eq!(model.meshes[0].faces, vec![[0, 1, 2], [0, 2, 3]]);
+    assert!(model.meshes[0].normals.is_some());
+    assert_eq!(model.meshes[1].faces, vec![[0, 1, 2]]);
+    assert!(model.meshes[1].normals.is_none());
+
+    match ObjModel::parse("v 0 0 0\nv 1 0 0\nf 1 2 3\n", "bad.obj") {
+        Err(ObjError::Parse {line, ..}) => assert_eq!(line, 3),
+        _ => panic!("expected a parse error"),
+    }
+    match ObjModel::parse("v 0 0 0\nv nan 0 0\n", "nan.obj") {
+        Err(ObjError::Parse {line, ..}) => assert_eq!(line, 2),
+        _ => panic!("expected a parse error"),
+    }
+    // unsupported statements are skipped, vertex colors ignored
+    let src = "v 0 0 0 1 0 0\nv 1 0 0 0 1 0\nv 0 1 0 0 0 1\nvp 0.5 0.5\nmg 1 0.5\nlod 2\nf 1 2 3\n";
+    assert_eq!(ObjModel::parse(src, "extra.obj").unwrap().meshes[0].faces, vec![[0, 1, 2]]);
+
+    let mtl = parse_mtl("newmtl red\nKd 1 0 0\nnewmtl glass\nNi 1.4\nd 0.2\n", "test.mtl").unwrap();
+    assert_eq!(format!("{:?}", mtl["red"]), format!("{:?}", LambertDiffuse::new(Color::new(1.0, 0.0, 0.0))));
+    assert_eq!(format!("{:?}", mtl["glass"]), format!("{:?}", Dielectric::new(1.4)));
+}
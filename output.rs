@@ -0,0 +1,206 @@
+use crate::{Color, Image, PostProcess, Transfer};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    Png,
+    Ppm,
+    Hdr,
+    Pfm,
+    Exr,
+}
+
+impl ImageFormat {
+    pub fn from_name(name: &str) -> Option<ImageFormat> {
+        match name.to_ascii_lowercase().as_str() {
+            "png" => Some(ImageFormat::Png),
+            "ppm" => Some(ImageFormat::Ppm),
+            "hdr" => Some(ImageFormat::Hdr),
+            "pfm" => Some(ImageFormat::Pfm),
+            "exr" => Some(ImageFormat::Exr),
+            _ => None,
+        }
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<ImageFormat> {
+        ImageFormat::from_name(path.as_ref().extension()?.to_str()?)
+    }
+}
+
+impl Image {
+    pub fn write_png<W: Write>(&self, post: &PostProcess, out: W) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(out, self.width() as u32, self.height() as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(io::Error::other)?;
+        writer.write_image_data(&post.to_rgb8(self)).map_err(io::Error::other)?;
+        writer.finish().map_err(io::Error::other)
+    }
+
+    // binary P6
+    pub fn write_ppm<W: Write>(&self, post: &PostProcess, mut out: W) -> io::Result<()> {
+        write!(out, "P6\n{} {}\n255\n", self.width(), self.height())?;
+        out.write_all(&post.to_rgb8(self))?;
+        out.flush()
+    }
+
+    // post processing only applies to the 8-bit formats, the float ones get raw radiance
+    pub fn write<W: Write>(&self, format: ImageFormat, post: &PostProcess, out: W) -> io::Result<()> {
+        match format {
+            ImageFormat::Png => self.write_png(post, out),
+            ImageFormat::Ppm => self.write_ppm(post, out),
+            ImageFormat::Hdr => self.write_hdr(out),
+            ImageFormat::Pfm => self.write_pfm(out),
+            ImageFormat::Exr => self.write_exr(out),
+        }
+    }
+
+    // the format is picked from the file extension
+    pub fn save<P: AsRef<Path>>(&self, path: P, post: &PostProcess) -> io::Result<()> {
+        let path = path.as_ref();
+        let format = ImageFormat::from_path(path).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("unknown image format for {}", path.display()))
+        })?;
+        self.write(format, post, BufWriter::new(File::create(path)?))
+    }
+
+    // 8 and 16-bit png of any color type, decoded from sRGB to linear
+    pub fn read_png<R: Read>(input: R) -> io::Result<Image> {
+        let mut decoder = png::Decoder::new(input);
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder.read_info().map_err(io::Error::other)?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).map_err(io::Error::other)?;
+        let channels = info.color_type.samples();
+        let bytes = &buf[..info.buffer_size()];
+        let pixels = bytes.chunks_exact(channels).map(|px| match px.len() {
+            1 | 2 => decode_rgb8([px[0], px[0], px[0]]),
+            _ => decode_rgb8([px[0], px[1], px[2]]),
+        });
+        Ok(image_from(info.width as usize, info.height as usize, pixels))
+    }
+
+    // binary P6 or plain P3
+    pub fn read_ppm<R: Read>(mut input: R) -> io::Result<Image> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, format!("ppm: {}", msg));
+        let mut data = Vec::new();
+        input.read_to_end(&mut data)?;
+
+        // header fields are separated by whitespace, with # comments up to the line end
+        let mut pos = 0;
+        let next_token = |pos: &mut usize| -> Option<String> {
+            loop {
+                match data.get(*pos)? {
+                    b'#' => while data.get(*pos).is_some_and(|&c| c != b'\n') { *pos += 1 },
+                    c if c.is_ascii_whitespace() => *pos += 1,
+                    _ => break,
+                }
+            }
+            let start = *pos;
+            while data.get(*pos).is_some_and(|c| !c.is_ascii_whitespace()) {
+                *pos += 1;
+            }
+            Some(String::from_utf8_lossy(&data[start..*pos]).into_owned())
+        };
+        let magic = next_token(&mut pos).ok_or_else(|| invalid("empty file"))?;
+        let mut header = [0usize; 3];
+        for x in header.iter_mut() {
+            *x = next_token(&mut pos).and_then(|t| t.parse().ok()).ok_or_else(|| invalid("bad header"))?;
+        }
+        let [width, height, max] = header;
+        if max == 0 || max > 65535 {
+            return Err(invalid("bad max value"));
+        }
+        let count = width
+            .checked_mul(height)
+            .and_then(|n| n.checked_mul(3))
+            .ok_or_else(|| invalid("image too large"))?;
+        // every value takes at least a byte, so a header can't ask for more than the file holds
+        if count > data.len() - pos {
+            return Err(invalid("truncated pixel data"));
+        }
+        let values: Vec<usize> = match magic.as_str() {
+            "P6" => {
+                // exactly one whitespace byte ends the header
+                let raster = data.get(pos + 1..).unwrap_or(&[]);
+                if max < 256 {
+                    raster.iter().take(count).map(|&b| b as usize).collect()
+                } else {
+                    raster.chunks_exact(2).take(count).map(|b| (b[0] as usize) << 8 | b[1] as usize).collect()
+                }
+            },
+            "P3" => (0..count).map_while(|_| next_token(&mut pos).and_then(|t| t.parse().ok())).collect(),
+            _ => return Err(invalid("only P3 and P6 are supported")),
+        };
+        if values.len() < count {
+            return Err(invalid("truncated pixel data"));
+        }
+        let decode = |x: usize| Transfer::Srgb.decode(x.min(max) as f64 / max as f64);
+        let pixels = values.chunks_exact(3).map(|c| Color::new(decode(c[0]), decode(c[1]), decode(c[2])));
+        Ok(image_from(width, height, pixels))
+    }
+
+    // png or ppm, picked from the file extension
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Image> {
+        let path = path.as_ref();
+        let file = BufReader::new(File::open(path)?);
+        match ImageFormat::from_path(path) {
+            Some(ImageFormat::Png) => Image::read_png(file),
+            Some(ImageFormat::Ppm) => Image::read_ppm(file),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("can't read {}, only png and ppm", path.display()))),
+        }
+    }
+}
+
+fn decode_rgb8(rgb: [u8; 3]) -> Color {
+    let decode = |x: u8| Transfer::Srgb.decode(x as f64 / 255.0);
+    Color::new(decode(rgb[0]), decode(rgb[1]), decode(rgb[2]))
+}
+
+fn image_from(width: usize, height: usize, pixels: impl Iterator<Item = Color>) -> Image {
+    let mut image = Image::new(width, height);
+    for (dst, src) in image.pixels_mut().iter_mut().zip(pixels) {
+        *dst = src;
+    }
+    image
+}
+
+#[test]
+fn test_write_ppm() {
+    let mut img = Image::new(2, 1);
+    img.set(1, 0, crate::Color::new(1.0, 0.25, 0.0));
+    let post = PostProcess {transfer: crate::Transfer::Gamma(2.0), dither: false, ..PostProcess::default()};
+    let mut out = Vec::new();
+    img.write_ppm(&post, &mut out).unwrap();
+    assert_eq!(out, b"P6\n2 1\n255\n\x00\x00\x00\xff\x80\x00");
+}
+
+#[test]
+fn test_read_images() {
+    let mut img = Image::new(3, 2);
+    img.set(0, 0, Color::new(1.0, 0.5, 0.0));
+    img.set(2, 1, Color::new(0.2, 0.0, 0.8));
+    let post = PostProcess {dither: false, ..PostProcess::default()};
+    let close = |a: &Image, b: &Image| a.pixels().iter().zip(b.pixels()).all(|(x, y)| (*x - *y).len() < 0.01);
+
+    let mut ppm = Vec::new();
+    img.write_ppm(&post, &mut ppm).unwrap();
+    let read = Image::read_ppm(&ppm[..]).unwrap();
+    assert_eq!((read.width(), read.height()), (3, 2));
+    assert!(close(&read, &img));
+
+    let mut png = Vec::new();
+    img.write_png(&post, &mut png).unwrap();
+    assert_eq!(Image::read_png(&png[..]).unwrap(), read);
+
+    let plain = Image::read_ppm(&b"P3\n# comment\n1 1 15\n15 0 15\n"[..]).unwrap();
+    assert_eq!(plain.get(0, 0), Color::new(1.0, 0.0, 1.0));
+    assert!(Image::read_ppm(&b"P6\n2 2 255\n\x00\x00"[..]).is_err());
+    // hostile sizes fail before anything gets allocated for them
+    for header in [&b"P6\n99999999999 99999999999 255\n\x00"[..], &b"P3\n100000 100000 255\n0 0 0\n"[..]].iter() {
+        let err = Image::read_ppm(*header).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
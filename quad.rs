@@ -0,0 +1,312 @@
+use crate::{Ray, Point, Vec3, Hittable, HitRecord, Material, Aabb, Sampler};
+use crate::triangle::area_pdf;
+use std::sync::Arc;
+
+const EPSILON: f64 = 1e-9;
+// flat shapes get a thin box so the slab test never sees a zero-width axis
+const BOX_PADDING: f64 = 1e-4;
+
+// Rectangle perpendicular to the AXIS axis at coordinate k, spanning [a0, a1] x [b0, b1]
+// along the other two axes in x, y, z order. The normal points along +AXIS.
+pub struct AxisRect<const AXIS: usize> {
+    a: (f64, f64),
+    b: (f64, f64),
+    k: f64,
+    material: Arc<dyn Material>,
+}
+
+pub type YZRect = AxisRect<0>;
+pub type XZRect = AxisRect<1>;
+pub type XYRect = AxisRect<2>;
+
+impl<const AXIS: usize> AxisRect<AXIS> {
+    const OTHER_AXES: (usize, usize) = match AXIS {
+        0 => (1, 2),
+        1 => (0, 2),
+        _ => (0, 1),
+    };
+
+    /// # Panics
+    ///
+    /// If either range is empty, with both bounds equal.
+    pub fn new(a: (f64, f64), b: (f64, f64), k: f64, material: Arc<dyn Material>) -> AxisRect<AXIS> {
+        assert!(a.0 != a.1 && b.0 != b.1, "rectangle bounds can't be equal");
+        AxisRect {a, b, k, material}
+    }
+
+    fn area(&self) -> f64 {
+        (self.a.1 - self.a.0).abs() * (self.b.1 - self.b.0).abs()
+    }
+
+    fn point(&self, a: f64, b: f64) -> Point {
+        let (ia, ib) = Self::OTHER_AXES;
+        let mut p = [0.0; 3];
+        p[AXIS] = self.k;
+        p[ia] = a;
+        p[ib] = b;
+        Point::new(p[0], p[1], p[2])
+    }
+}
+
+impl<const AXIS: usize> Hittable for AxisRect<AXIS> {
+    fn hit(&self, ray: &Ray, min_t: f64, max_t: f64) -> Option<HitRecord<'_>> {
+        if ray.dir[AXIS].abs() < EPSILON {
+            return None;
+        }
+        let t = (self.k - ray.origin[AXIS]) / ray.dir[AXIS];
+        if t < min_t || t > max_t {
+            return None;
+        }
+        let (ia, ib) = Self::OTHER_AXES;
+        let p = ray.at(t);
+        let u = (p[ia] - self.a.0) / (self.a.1 - self.a.0);
+        let v = (p[ib] - self.b.0) / (self.b.1 - self.b.0);
+        if !(0.0..=1.0).contains(&u) || !(0.0..=1.0).contains(&v) {
+            return None;
+        }
+        let mut normal = [0.0; 3];
+        normal[AXIS] = 1.0;
+        let normal = Vec3::new(normal[0], normal[1], normal[2]);
+        Some(HitRecord::new(ray, t, normal, (u, v), &*self.material, self))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let pad = Vec3::new(BOX_PADDING, BOX_PADDING, BOX_PADDING);
+        let (p, q) = (self.point(self.a.0, self.b.0), self.point(self.a.1, self.b.1));
+        Some(Aabb::new(Vec3::min(&p, &q) - pad, Vec3::max(&p, &q) + pad))
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_light()
+    }
+
+    fn sample_direction(&self, origin: Point, _time: f64, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        let (s, t) = sampler.next_2d();
+        let p = self.point(self.a.0 + s * (self.a.1 - self.a.0), self.b.0 + t * (self.b.1 - self.b.0));
+        Some(p - origin)
+    }
+
+    fn direction_pdf(&self, origin: Point, dir: Vec3, _time: f64) -> f64 {
+        area_pdf(self.hit(&Ray::new(origin, dir.unit()), 0.0, f64::INFINITY), self.area())
+    }
+}
+
+// Parallelogram with a corner at origin and sides u and v, uv coordinates run
+// from 0 to 1 along the sides. The normal is u x v.
+pub struct Quad {
+    origin: Point,
+    u: Vec3,
+    v: Vec3,
+    normal: Vec3,
+    // scaled normal that turns a point of the plane into its (u, v) coordinates
+    w: Vec3,
+    area: f64,
+    material: Arc<dyn Material>,
+}
+
+impl Quad {
+    /// # Panics
+    ///
+    /// If `u` or `v` is zero or they're parallel.
+    pub fn new(origin: Point, u: Vec3, v: Vec3, material: Arc<dyn Material>) -> Quad {
+        let n = Vec3::cross(&u, &v);
+        assert!(n.len_sq() > 0.0, "quad sides can't be zero or parallel");
+        Quad {origin, u, v, normal: n.unit(), w: n / n.len_sq(), area: n.len(), material}
+    }
+
+    pub fn hit_at(&self, ray: &Ray, min_t: f64, max_t: f64) -> Option<HitRecord<'_>> {
+        let denom = Vec3::dot(&self.normal, &ray.dir);
+        if denom.abs() < EPSILON {
+            return None;
+        }
+        let t = Vec3::dot(&self.normal, &(self.origin - ray.origin)) / denom;
+        if t < min_t || t > max_t {
+            return None;
+        }
+        let planar = ray.at(t) - self.origin;
+        let alpha = Vec3::dot(&self.w, &Vec3::cross(&planar, &self.v));
+        let beta = Vec3::dot(&self.w, &Vec3::cross(&self.u, &planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+        Some(HitRecord::new(ray, t, self.normal, (alpha, beta), &*self.material, self))
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, ray: &Ray, min_t: f64, max_t: f64) -> Option<HitRecord<'_>> {
+        self.hit_at(ray, min_t, max_t)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let pad = Vec3::new(BOX_PADDING, BOX_PADDING, BOX_PADDING);
+        let corners = [self.origin, self.origin + self.u, self.origin + self.v, self.origin + self.u + self.v];
+        let min = corners.iter().fold(corners[0], |acc, p| Vec3::min(&acc, p));
+        let max = corners.iter().fold(corners[0], |acc, p| Vec3::max(&acc, p));
+        Some(Aabb::new(min - pad, max + pad))
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_light()
+    }
+
+    fn sample_direction(&self, origin: Point, _time: f64, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        let (s, t) = sampler.next_2d();
+        Some(self.origin + s * self.u + t * self.v - origin)
+    }
+
+    fn direction_pdf(&self, origin: Point, dir: Vec3, _time: f64) -> f64 {
+        area_pdf(self.hit_at(&Ray::new(origin, dir.unit()), 0.0, f64::INFINITY), self.area)
+    }
+}
+
+// Infinite plane through point. It has no bounding box, so the scene tests it
+// against every ray outside the BVH. uv are distances along two directions in the
+// plane, so textures repeat every unit.
+pub struct Plane {
+    point: Point,
+    normal: Vec3,
+    tangents: (Vec3, Vec3),
+    material: Arc<dyn Material>,
+}
+
+impl Plane {
+    pub fn new(point: Point, normal: Vec3, material: Arc<dyn Material>) -> Plane {
+        let normal = normal.unit();
+        Plane {point, normal, tangents: normal.basis(), material}
+    }
+}
+
+// an emitting plane isn't registered as an area light, it can't be sampled
+impl Hittable for Plane {
+    fn hit(&self, ray: &Ray, min_t: f64, max_t: f64) -> Option<HitRecord<'_>> {
+        let denom = Vec3::dot(&self.normal, &ray.dir);
+        if denom.abs() < EPSILON {
+            return None;
+        }
+        let t = Vec3::dot(&self.normal, &(self.point - ray.origin)) / denom;
+        if t < min_t || t > max_t {
+            return None;
+        }
+        let planar = ray.at(t) - self.point;
+        let uv = (Vec3::dot(&planar, &self.tangents.0), Vec3::dot(&planar, &self.tangents.1));
+        Some(HitRecord::new(ray, t, self.normal, uv, &*self.material, self))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+}
+
+// Axis-aligned box made of six outward facing quads.
+pub struct Cuboid {
+    faces: [Quad; 6],
+    bbox: Aabb,
+    material: Arc<dyn Material>,
+}
+
+impl Cuboid {
+    /// # Panics
+    ///
+    /// If `a` and `b` share a coordinate, which makes the box flat.
+    pub fn new(a: Point, b: Point, material: Arc<dyn Material>) -> Cuboid {
+        let (min, max) = (Vec3::min(&a, &b), Vec3::max(&a, &b));
+        let d = max - min;
+        assert!(d.get_x() > 0.0 && d.get_y() > 0.0 && d.get_z() > 0.0, "box corners can't share a coordinate");
+        let (dx, dy, dz) = (Vec3::new(d.get_x(), 0.0, 0.0), Vec3::new(0.0, d.get_y(), 0.0), Vec3::new(0.0, 0.0, d.get_z()));
+        let p = Point::new;
+        let quad = |origin, u, v| Quad::new(origin, u, v, material.clone());
+        let faces = [
+            quad(p(min.get_x(), min.get_y(), max.get_z()), dx, dy),
+            quad(p(max.get_x(), min.get_y(), max.get_z()), -1.0 * dz, dy),
+            quad(p(max.get_x(), min.get_y(), min.get_z()), -1.0 * dx, dy),
+            quad(min, dz, dy),
+            quad(p(min.get_x(), max.get_y(), max.get_z()), dx, -1.0 * dz),
+            quad(min, dx, dz),
+        ];
+        Cuboid {faces, bbox: Aabb::new(min, max), material}
+    }
+
+    fn area(&self) -> f64 {
+        self.faces.iter().map(|f| f.area).sum()
+    }
+}
+
+impl Hittable for Cuboid {
+    fn hit(&self, ray: &Ray, min_t: f64, max_t: f64) -> Option<HitRecord<'_>> {
+        let mut hr = self.faces.iter().fold(None, |cur_hit: Option<HitRecord>, face| {
+            let cur_max_t = cur_hit.as_ref().map_or(max_t, |hr| hr.t);
+            face.hit_at(ray, min_t, cur_max_t).or(cur_hit)
+        })?;
+        hr.object = self;
+        Some(hr)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let pad = Vec3::new(BOX_PADDING, BOX_PADDING, BOX_PADDING);
+        Some(Aabb::new(self.bbox.min - pad, self.bbox.max + pad))
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_light()
+    }
+
+    // faces are picked proportionally to their area
+    fn sample_direction(&self, origin: Point, time: f64, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        let mut target = sampler.next_1d() * self.area();
+        for face in &self.faces[..5] {
+            if target < face.area {
+                return face.sample_direction(origin, time, sampler);
+            }
+            target -= face.area;
+        }
+        self.faces[5].sample_direction(origin, time, sampler)
+    }
+
+    fn direction_pdf(&self, origin: Point, dir: Vec3, time: f64) -> f64 {
+        let area = self.area();
+        if area <= 0.0 {
+            return 0.0;
+        }
+        self.faces.iter().map(|f| f.area / area * f.direction_pdf(origin, dir, time)).sum()
+    }
+}
+
+#[test]
+fn test_planar_shapes() {
+    let material: Arc<dyn Material> = Arc::new(crate::Normal);
+    let down = |x, z| Ray::new(Point::new(x, 5.0, z), Vec3::new(0.0, -1.0, 0.0));
+
+    let rect = XZRect::new((0.0, 2.0), (0.0, 4.0), 1.0, material.clone());
+    let hr = rect.hit(&down(0.5, 1.0), 0.0, f64::INFINITY).unwrap();
+    assert_eq!((hr.t, hr.u, hr.v), (4.0, 0.25, 0.25));
+    assert_eq!(hr.normal, Vec3::new(0.0, 1.0, 0.0));
+    assert!(rect.hit(&down(2.5, 1.0), 0.0, f64::INFINITY).is_none());
+
+    let quad = Quad::new(Point::new(0.0, 1.0, 0.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 4.0), material.clone());
+    let hr = quad.hit(&down(0.5, 1.0), 0.0, f64::INFINITY).unwrap();
+    assert_eq!((hr.t, hr.u, hr.v), (4.0, 0.25, 0.25));
+    // u x v points down, the ray comes from the back
+    assert!(!hr.front_face);
+    assert!(quad.hit(&down(0.5, -1.0), 0.0, f64::INFINITY).is_none());
+
+    let plane = Plane::new(Point::new(0.0, 1.0, 0.0), Vec3::new(0.0, 2.0, 0.0), material.clone());
+    assert_eq!(plane.hit(&down(100.0, -70.0), 0.0, f64::INFINITY).unwrap().t, 4.0);
+
+    let cuboid = Cuboid::new(Point::new(1.0, 1.0, 1.0), Point::new(-1.0, -1.0, -1.0), material);
+    assert_eq!(cuboid.area(), 24.0);
+    let hr = cuboid.hit(&down(0.0, 0.0), 0.0, f64::INFINITY).unwrap();
+    assert_eq!((hr.t, hr.normal), (4.0, Vec3::new(0.0, 1.0, 0.0)));
+    assert!(hr.front_face);
+    let inside = cuboid.hit(&Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0)), 0.0, f64::INFINITY).unwrap();
+    assert_eq!(inside.t, 1.0);
+    assert!(!inside.front_face);
+}
+
+#[test]
+#[should_panic(expected = "quad sides can't be zero or parallel")]
+fn test_parallel_quad_sides() {
+    use crate::{LambertDiffuse, Color};
+    let u = Vec3::new(1.0, 2.0, 0.0);
+    Quad::new(Point::new(0.0, 0.0, 0.0), u, -2.0 * u, Arc::new(LambertDiffuse::new(Color::new(1.0, 1.0, 1.0))));
+}
@@ -1,9 +1,10 @@
-use crate::vec::{Point, Color, Vec3};
-use crate::scene::Scene;
+use crate::vec::{Point, Vec3};
 
 pub struct Ray {
     pub origin: Point,
     pub dir: Vec3,
+    // moving objects are intersected where they are at this time
+    pub time: f64,
 }
 
 impl Ray {
@@ -12,14 +13,10 @@ impl Ray {
     }
 
     pub fn new(origin: Point, dir: Vec3) -> Ray {
-        Ray {origin, dir}
+        Ray {origin, dir, time: 0.0}
     }
 
-    pub fn ray_color(&self, scene: &Scene, max_depth: u32) -> Color {
-        if let Some(color) = scene.hit(self, max_depth) {
-            color
-        } else {
-            scene.bg_color(&self.dir)
-        }
+    pub fn with_time(origin: Point, dir: Vec3, time: f64) -> Ray {
+        Ray {origin, dir, time}
     }
 }
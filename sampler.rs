@@ -0,0 +1,310 @@
+use crate::Vec3;
+use rand::Rng;
+use rand_pcg::Pcg32;
+use std::f64::consts::PI;
+
+// Source of the [0, 1) numbers for one sample of one pixel. Every call moves on to
+// the next dimension, so the camera, lights and materials must draw in a fixed order
+// for the low-discrepancy samplers to line up samples of the same pixel.
+pub trait Sampler {
+    fn next_1d(&mut self) -> f64;
+
+    fn next_2d(&mut self) -> (f64, f64) {
+        (self.next_1d(), self.next_1d())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SamplerKind {
+    Random,
+    Stratified,
+    Halton,
+    Sobol,
+}
+
+impl SamplerKind {
+    pub fn from_name(name: &str) -> Option<SamplerKind> {
+        match name.to_ascii_lowercase().as_str() {
+            "random" => Some(SamplerKind::Random),
+            "stratified" => Some(SamplerKind::Stratified),
+            "halton" => Some(SamplerKind::Halton),
+            "sobol" => Some(SamplerKind::Sobol),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SamplerKind::Random => "random",
+            SamplerKind::Stratified => "stratified",
+            SamplerKind::Halton => "halton",
+            SamplerKind::Sobol => "sobol",
+        }
+    }
+
+    // sampler for sample index out of count taken in a pixel, the same arguments
+    // always give the same numbers
+    pub fn start(&self, seed: u64, pixel: usize, index: u32, count: u32) -> Box<dyn Sampler> {
+        let pixel_seed = mix(seed, pixel as u64);
+        let rng = Pcg32::new(pixel_seed, index as u64);
+        match self {
+            SamplerKind::Random => Box::new(RandomSampler {rng}),
+            SamplerKind::Stratified => Box::new(StratifiedSampler {pixel_seed, index, count, dim: 0, rng}),
+            SamplerKind::Halton => Box::new(HaltonSampler {pixel_seed, index, dim: 0, rng}),
+            SamplerKind::Sobol => Box::new(SobolSampler {pixel_seed, index, dim: 0}),
+        }
+    }
+}
+
+pub struct RandomSampler {
+    rng: Pcg32,
+}
+
+impl Sampler for RandomSampler {
+    fn next_1d(&mut self) -> f64 {
+        self.rng.gen_range(0.0, 1.0)
+    }
+}
+
+// Jittered strata, shuffled independently in every dimension so the dimensions
+// don't correlate with each other.
+pub struct StratifiedSampler {
+    pixel_seed: u64,
+    index: u32,
+    count: u32,
+    dim: u64,
+    rng: Pcg32,
+}
+
+impl StratifiedSampler {
+    fn stratum(&mut self, strata: u32) -> u32 {
+        let seed = mix(self.pixel_seed, self.dim) as u32;
+        self.dim += 1;
+        permutation_element(self.index % strata, strata, seed)
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn next_1d(&mut self) -> f64 {
+        let n = self.count.max(1);
+        let s = self.stratum(n);
+        (s as f64 + self.rng.gen_range(0.0, 1.0)) / n as f64
+    }
+
+    // a grid of at least count cells, each holding at most one sample
+    fn next_2d(&mut self) -> (f64, f64) {
+        let n = self.count.max(1);
+        let nx = (n as f64).sqrt().ceil() as u32;
+        let ny = n.div_ceil(nx);
+        let s = self.stratum(nx * ny);
+        let x = ((s % nx) as f64 + self.rng.gen_range(0.0, 1.0)) / nx as f64;
+        let y = ((s / nx) as f64 + self.rng.gen_range(0.0, 1.0)) / ny as f64;
+        (x, y)
+    }
+}
+
+const PRIMES: [u32; 48] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89,
+    97, 101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193, 197, 199, 211, 223,
+];
+
+// Halton points, randomized per pixel by a random shift in every dimension. Past
+// the prime table the higher bases are too poorly distributed to be worth it, so
+// the remaining dimensions are plain random.
+pub struct HaltonSampler {
+    pixel_seed: u64,
+    index: u32,
+    dim: u64,
+    rng: Pcg32,
+}
+
+impl Sampler for HaltonSampler {
+    fn next_1d(&mut self) -> f64 {
+        let dim = self.dim;
+        self.dim += 1;
+        match PRIMES.get(dim as usize) {
+            Some(&base) => {
+                let shift = to_unit(mix(self.pixel_seed, dim));
+                (radical_inverse(base, self.index) + shift).fract()
+            },
+            None => self.rng.gen_range(0.0, 1.0),
+        }
+    }
+}
+
+// Owen-scrambled 2D Sobol points, with every pair of dimensions shuffled and
+// scrambled independently (Burley 2020, "Practical Hash-based Owen Scrambling").
+pub struct SobolSampler {
+    pixel_seed: u64,
+    index: u32,
+    dim: u64,
+}
+
+impl SobolSampler {
+    fn next_seed(&mut self) -> u32 {
+        let seed = mix(self.pixel_seed, self.dim) as u32;
+        self.dim += 1;
+        seed
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn next_1d(&mut self) -> f64 {
+        let seed = self.next_seed();
+        let index = nested_uniform_scramble(self.index, seed);
+        let x = nested_uniform_scramble(index.reverse_bits(), hash_u32(seed, 0));
+        x as f64 / 4294967296.0
+    }
+
+    fn next_2d(&mut self) -> (f64, f64) {
+        let seed = self.next_seed();
+        let index = nested_uniform_scramble(self.index, seed);
+        let x = nested_uniform_scramble(index.reverse_bits(), hash_u32(seed, 0));
+        let y = nested_uniform_scramble(sobol_second_dim(index), hash_u32(seed, 1));
+        (x as f64 / 4294967296.0, y as f64 / 4294967296.0)
+    }
+}
+
+// uniform direction from a 2D sample
+pub fn sample_sphere((u, v): (f64, f64)) -> Vec3 {
+    let z = 1.0 - 2.0 * u;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * v;
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+// uniform point inside the unit ball
+pub fn sample_ball(sampler: &mut dyn Sampler) -> Vec3 {
+    let r = sampler.next_1d().cbrt();
+    r * sample_sphere(sampler.next_2d())
+}
+
+// uniform point on the unit disk, concentric mapping so strata stay compact
+pub fn sample_disk((u, v): (f64, f64)) -> (f64, f64) {
+    let (a, b) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+    if a == 0.0 && b == 0.0 {
+        return (0.0, 0.0);
+    }
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, PI / 4.0 * (b / a))
+    } else {
+        (b, PI / 2.0 - PI / 4.0 * (a / b))
+    };
+    (r * theta.cos(), r * theta.sin())
+}
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+fn mix(seed: u64, x: u64) -> u64 {
+    splitmix64(seed ^ splitmix64(x))
+}
+
+fn hash_u32(seed: u32, x: u32) -> u32 {
+    mix(seed as u64, x as u64) as u32
+}
+
+fn to_unit(x: u64) -> f64 {
+    (x >> 11) as f64 / (1u64 << 53) as f64
+}
+
+fn radical_inverse(base: u32, mut index: u32) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let (mut result, mut scale) = (0.0, inv_base);
+    while index > 0 {
+        result += (index % base) as f64 * scale;
+        index /= base;
+        scale *= inv_base;
+    }
+    result
+}
+
+// second Sobol dimension, its direction numbers are each the previous one xor itself shifted
+fn sobol_second_dim(mut index: u32) -> u32 {
+    let (mut v, mut result) = (1u32 << 31, 0);
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= v;
+        }
+        index >>= 1;
+        v ^= v >> 1;
+    }
+    result
+}
+
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x
+}
+
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+// element i of a random permutation of 0..len picked by seed (Kensler 2013)
+fn permutation_element(mut i: u32, len: u32, seed: u32) -> u32 {
+    let mut w = len - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= seed >> 16;
+        i ^= (i & w) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= seed >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < len {
+            return (i.wrapping_add(seed)) % len;
+        }
+    }
+}
+
+#[test]
+fn test_samplers_are_stratified() {
+    // 16 samples of a pixel put exactly one point in each cell of a 4x4 grid
+    for kind in [SamplerKind::Stratified, SamplerKind::Sobol] {
+        for pixel in 0..3 {
+            let mut cells = [0; 16];
+            for i in 0..16 {
+                let mut sampler = kind.start(7, pixel, i, 16);
+                sampler.next_1d();
+                let (x, y) = sampler.next_2d();
+                assert!((0.0..1.0).contains(&x) && (0.0..1.0).contains(&y));
+                cells[(y * 4.0) as usize * 4 + (x * 4.0) as usize] += 1;
+            }
+            assert_eq!(cells, [1; 16], "{}", kind.name());
+        }
+    }
+
+    let mut strata = [0; 8];
+    for i in 0..8 {
+        strata[(SamplerKind::Stratified.start(1, 0, i, 8).next_1d() * 8.0) as usize] += 1;
+    }
+    assert_eq!(strata, [1; 8]);
+
+    assert_eq!(radical_inverse(2, 3), 0.75);
+    assert!((radical_inverse(3, 5) - 7.0 / 9.0).abs() < 1e-12);
+}
@@ -1,53 +1,240 @@
-use crate::{Ray, Point, Vec3, Color};
-use rand::Rng;
+use crate::{Ray, Point, Vec3, Color, Aabb, BvhNode, Light, Material, LambertDiffuse, Reflect, Dielectric, Sampler, sample_ball};
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg32;
+use std::f64::consts::PI;
+use std::sync::{Arc, OnceLock};
 
 pub trait Hittable: Send + Sync {
-    fn hit(&self, ray: &Ray, scene: &Scene, min_t: f64, max_t: f64, recursions: u32) -> Option<(Color, HitRecord)>;
+    fn hit(&self, ray: &Ray, min_t: f64, max_t: f64) -> Option<HitRecord<'_>>;
+
+    fn bounding_box(&self) -> Option<Aabb>;
+
+    // Like hit, for objects stopping rays at random points like fog, which take the
+    // random numbers from the sampler. Containers pass it on to what they hold.
+    fn hit_sampled(&self, ray: &Ray, min_t: f64, max_t: f64, _sampler: &mut dyn Sampler) -> Option<HitRecord<'_>> {
+        self.hit(ray, min_t, max_t)
+    }
+
+    // Every point in the range where the ray crosses the surface, by increasing t, with
+    // front_face telling whether it enters the object there. CSG builds on this, so
+    // objects used in it should be closed. By default found by repeated hits.
+    fn crossings(&self, ray: &Ray, min_t: f64, max_t: f64) -> Vec<HitRecord<'_>> {
+        let mut crossings = Vec::new();
+        let mut t = min_t;
+        while let Some(hr) = self.hit(ray, t, max_t) {
+            // step past the last hit without skipping surfaces right behind it
+            t = hr.t + CROSSING_STEP * hr.t.abs().max(1.0);
+            crossings.push(hr);
+        }
+        crossings
+    }
+
+    // area lights are sampled directly from the surfaces they illuminate
+    fn is_light(&self) -> bool {
+        false
+    }
+
+    // direction from origin towards a random point of the surface at time, for light sampling
+    fn sample_direction(&self, _origin: Point, _time: f64, _sampler: &mut dyn Sampler) -> Option<Vec3> {
+        None
+    }
+
+    // solid angle density of sample_direction returning dir
+    fn direction_pdf(&self, _origin: Point, _dir: Vec3, _time: f64) -> f64 {
+        0.0
+    }
+}
+
+const CROSSING_STEP: f64 = 1e-9;
+
+// How a ray leaving a diffuse surface was sampled, so that an area light it ends up
+// hitting can weight its emission against light sampling at that surface.
+pub struct Bounce {
+    pub origin: Point,
+    pub pdf: f64,
+}
+
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b > 0.0 { a / (a + b) } else { 0.0 }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Background {
+    Sky,
+    Color(Color),
 }
 
 pub struct Scene {
     min_t: f64,
     max_t: f64,
-    objs: Vec<Box<dyn Hittable>>,
+    objs: Vec<Arc<dyn Hittable>>,
+    lights: Vec<Light>,
+    area_lights: Vec<Arc<dyn Hittable>>,
+    background: Background,
+    use_bvh: bool,
+    accel: OnceLock<SceneAccel>,
+}
+
+struct SceneAccel {
+    bvh: Option<BvhNode>,
+    unbounded: Vec<Arc<dyn Hittable>>,
 }
 
 impl Scene {
     pub fn new(min_t: f64, max_t: f64) -> Scene {
-        Scene {min_t, max_t, objs: Vec::new()}
+        Scene {
+            min_t,
+            max_t,
+            objs: Vec::new(),
+            lights: Vec::new(),
+            area_lights: Vec::new(),
+            background: Background::Sky,
+            use_bvh: true,
+            accel: OnceLock::new(),
+        }
     }
 
     pub fn add(&mut self, obj: Box<dyn Hittable>) {
-        self.objs.push(obj);
+        self.add_shared(obj.into());
     }
 
-    pub fn hit(&self, ray: &Ray, depth: u32) -> Option<Color> {
-        if depth == 0 {
-            return Some(Color::new(0.0, 0.0, 0.0))
+    // for objects also used elsewhere, like instanced meshes
+    pub fn add_shared(&mut self, obj: Arc<dyn Hittable>) {
+        if obj.is_light() {
+            self.area_lights.push(obj.clone());
         }
-        self.objs
+        self.objs.push(obj);
+        self.accel = OnceLock::new();
+    }
+
+    pub fn add_light(&mut self, light: Light) {
+        self.lights.push(light);
+    }
+
+    pub fn set_background(&mut self, background: Background) {
+        self.background = background;
+    }
+
+    pub fn use_bvh(&mut self, enabled: bool) {
+        self.use_bvh = enabled;
+    }
+
+    // the BVH is built on the first hit after the objects change
+    fn accel(&self) -> &SceneAccel {
+        self.accel.get_or_init(|| {
+            let (bounded, unbounded): (Vec<_>, Vec<_>) =
+                self.objs.iter().cloned().partition(|o| o.bounding_box().is_some_and(|b| b.is_finite()));
+            let bvh = if bounded.is_empty() { None } else { Some(BvhNode::new(bounded)) };
+            SceneAccel {bvh, unbounded}
+        })
+    }
+
+    // closest hit along the ray
+    pub fn hit(&self, ray: &Ray, sampler: &mut dyn Sampler) -> Option<HitRecord<'_>> {
+        self.hit_within(ray, self.max_t, sampler)
+    }
+
+    fn hit_within(&self, ray: &Ray, max_t: f64, sampler: &mut dyn Sampler) -> Option<HitRecord<'_>> {
+        let (bvh, linear) = if self.use_bvh {
+            let accel = self.accel();
+            (accel.bvh.as_ref(), &accel.unbounded)
+        } else {
+            (None, &self.objs)
+        };
+
+        let bvh_hit = bvh.and_then(|bvh| bvh.hit_sampled(ray, self.min_t, max_t, sampler));
+        linear.iter().fold(bvh_hit, |cur_hit, o| {
+            let cur_max_t = cur_hit.as_ref().map_or(max_t, |hr| hr.t);
+            o.hit_sampled(ray, self.min_t, cur_max_t, sampler).or(cur_hit)
+        })
+    }
+
+    pub fn occluded(&self, ray: &Ray, max_t: f64, sampler: &mut dyn Sampler) -> bool {
+        self.hit_within(ray, max_t, sampler).is_some()
+    }
+
+    // light reflected at p from the point, spot and directional lights, eval gives
+    // the bsdf times the cosine for light arriving from a direction
+    pub fn direct_light(&self, p: Point, time: f64, eval: impl Fn(Vec3) -> Color, sampler: &mut dyn Sampler) -> Color {
+        self.lights
             .iter()
-            .fold((None, self.max_t), |(cur_hit, cur_max_t), o| {
-                let hit = o.hit(ray, self, self.min_t, cur_max_t, depth);
-                if let Some((color, hr)) = hit {
-                    (Some(color), hr.t)
+            .filter_map(|light| light.sample(p))
+            .fold(Color::new(0.0, 0.0, 0.0), |acc, sample| {
+                let f = eval(sample.dir);
+                // shorten shadow rays a bit so they don't hit whatever the light sits on
+                if f == Color::new(0.0, 0.0, 0.0) || self.occluded(&Ray::with_time(p, sample.dir, time), sample.dist * (1.0 - 1e-6), sampler) {
+                    acc
                 } else {
-                    (cur_hit, cur_max_t)
+                    acc + f * sample.irradiance
                 }
             })
-            .0
+    }
+
+    // MIS weighted estimate of the light reflected at p from one randomly picked area light
+    pub fn sample_area_light(
+        &self,
+        p: Point,
+        time: f64,
+        eval: impl Fn(Vec3) -> Color,
+        bsdf_pdf: impl Fn(Vec3) -> f64,
+        sampler: &mut dyn Sampler,
+    ) -> Color {
+        let black = Color::new(0.0, 0.0, 0.0);
+        if self.area_lights.is_empty() {
+            return black;
+        }
+        let n = self.area_lights.len();
+        let light = &self.area_lights[((sampler.next_1d() * n as f64) as usize).min(n - 1)];
+        let dir = match light.sample_direction(p, time, sampler) {
+            Some(dir) => dir.unit(),
+            None => return black,
+        };
+        let f = eval(dir);
+        let light_pdf = light.direction_pdf(p, dir, time) / n as f64;
+        if f == black || light_pdf <= 0.0 {
+            return black;
+        }
+        let ray = Ray::with_time(p, dir, time);
+        match light.hit(&ray, self.min_t, self.max_t) {
+            Some(hr) if !self.occluded(&ray, hr.t * (1.0 - 1e-6), sampler) => {
+                power_heuristic(light_pdf, bsdf_pdf(dir)) / light_pdf * f * hr.material.emitted(&hr)
+            },
+            _ => black,
+        }
+    }
+
+    // weight of an area light's emission when it's reached by a sampled bounce
+    pub fn emission_weight(&self, obj: &dyn Hittable, ray: &Ray, bounce: Option<&Bounce>) -> f64 {
+        match bounce {
+            Some(bounce) if !self.area_lights.is_empty() => {
+                let light_pdf = obj.direction_pdf(bounce.origin, ray.dir.unit(), ray.time) / self.area_lights.len() as f64;
+                power_heuristic(bounce.pdf, light_pdf)
+            },
+            _ => 1.0,
+        }
     }
 
     pub fn bg_color(&self, dir: &Vec3) -> Color {
-        let y = 0.5 * (dir.unit().get_y() + 1.0);
-        (1.0 - y) * Color::new(1.0, 1.0, 1.0) + y * Color::new(0.5, 0.7, 1.0)
+        match self.background {
+            Background::Sky => {
+                let y = 0.5 * (dir.unit().get_y() + 1.0);
+                (1.0 - y) * Color::new(1.0, 1.0, 1.0) + y * Color::new(0.5, 0.7, 1.0)
+            },
+            Background::Color(color) => color,
+        }
     }
 
-    pub fn fill_random(&mut self, side_count: u32) {
-        let earth = Sphere::new(Vec3::new(0.0, -1000.0, 0.0), 1000.0, ColorBehavior::LambertDiffuse(Color::new(0.5, 0.5, 0.5)));
-        self.add(Box::new(earth));
+    pub fn fill_random(&mut self, side_count: u32, seed: u64) {
+        self.fill_random_with(side_count, &mut Pcg32::seed_from_u64(seed));
+    }
 
-        let mut rng = rand::thread_rng();
+    pub fn fill_random_with<R: Rng>(&mut self, side_count: u32, rng: &mut R) {
+        let earth = Sphere::new(Vec3::new(0.0, -1000.0, 0.0), 1000.0, Arc::new(LambertDiffuse::new(Color::new(0.5, 0.5, 0.5))));
+        self.add(Box::new(earth));
 
+        // all the glass spheres share one material
+        let glass: Arc<dyn Material> = Arc::new(Dielectric::new(1.5));
         for i in -(side_count as i32 / 2)..(side_count as i32 / 2) {
             for j in -(side_count as i32 / 2)..(side_count as i32 / 2) {
                 let x = (i as f64) + 0.9 * rng.gen_range(0.0, 0.9);
@@ -59,18 +246,18 @@ impl Scene {
                     continue
                 }
 
-                let mat = match rng.gen_range(0.0, 1.0) {
+                let mat: Arc<dyn Material> = match rng.gen_range(0.0, 1.0) {
                     n if n < 0.8 => {
-                        let color1 = Color::random(0.0, 1.0);
-                        let color2 = Color::random(0.0, 1.0);
-                        ColorBehavior::LambertDiffuse(color1*color2)
+                        let color1 = Color::random(rng, 0.0, 1.0);
+                        let color2 = Color::random(rng, 0.0, 1.0);
+                        Arc::new(LambertDiffuse::new(color1*color2))
                     },
                     n if n < 0.95 => {
-                        let color = Color::random(0.5, 1.0);
+                        let color = Color::random(rng, 0.5, 1.0);
                         let fuzz = rng.gen_range(0.0, 0.5);
-                        ColorBehavior::Reflect(color, fuzz)
+                        Arc::new(Reflect::new(color, fuzz))
                     },
-                    _ => ColorBehavior::Dielectric(1.5),
+                    _ => glass.clone(),
                 };
 
                 self.add(Box::new(Sphere::new(center, 0.2, mat)));
@@ -78,59 +265,93 @@ impl Scene {
         }
 
         // center sphere - refract
-        let color = ColorBehavior::Dielectric(1.5);
-        let sphere = Sphere::new(Point::new(0.0, 1.0, 0.0), 1.0, color);
+        let sphere = Sphere::new(Point::new(0.0, 1.0, 0.0), 1.0, glass);
         self.add(Box::new(sphere));
 
         // left sphere - diffuse
-        let color = ColorBehavior::LambertDiffuse(Color::new(0.4, 0.2, 0.1));
+        let color = Arc::new(LambertDiffuse::new(Color::new(0.4, 0.2, 0.1)));
         let sphere = Sphere::new(Point::new(-4.0, 1.0, 0.0), 1.0, color);
         self.add(Box::new(sphere));
 
         // right sphere - reflect
-        let color = ColorBehavior::Reflect(Color::new(0.7, 0.6, 0.5), 0.0);
+        let color = Arc::new(Reflect::new(Color::new(0.7, 0.6, 0.5), 0.0));
         let sphere = Sphere::new(Point::new(4.0, 1.0, 0.0), 1.0, color);
         self.add(Box::new(sphere));
     }
 }
 
-pub enum ColorBehavior {
-    Normal,
-    Color(Color),
-    Diffuse,
-    LambertDiffuse(Color),
-    Reflect(Color, f64),
-    Dielectric(f64),
-}
-
 pub struct Sphere {
     center: Point,
+    // how far the center moves between times 0 and 1
+    motion: Vec3,
     radius: f64,
-    coloring: ColorBehavior,
+    material: Arc<dyn Material>,
 }
 
-pub struct HitRecord {
-    p: Point,
-    front_face: bool,
-    normal: Vec3,
-    t: f64,
-    ray_dir: Vec3,
+pub struct HitRecord<'a> {
+    pub p: Point,
+    pub front_face: bool,
+    pub normal: Vec3,
+    // normal of the surface itself, on the same side as normal, which can be a shading normal
+    pub geometric_normal: Vec3,
+    pub t: f64,
+    pub ray_dir: Vec3,
+    pub u: f64,
+    pub v: f64,
+    pub material: &'a dyn Material,
+    // the object area lights are sampled through, for weighting their emission
+    pub object: &'a dyn Hittable,
 }
 
-impl HitRecord {
-    pub fn reflect(&self, fuzz: f64) -> Vec3 {
+impl<'a> HitRecord<'a> {
+    pub fn new(
+        ray: &Ray,
+        t: f64,
+        outward_normal: Vec3,
+        (u, v): (f64, f64),
+        material: &'a dyn Material,
+        object: &'a dyn Hittable,
+    ) -> HitRecord<'a> {
+        let (normal, front_face) = if Vec3::dot(&outward_normal, &ray.dir) > 0.0 {
+            // ray in the same dir as the outward normal, so it comes from inside
+            (-1.0 * outward_normal, false)
+        } else {
+            // ray in the opposite dir from the outward normal, so it comes from outside
+            (outward_normal, true)
+        };
+        HitRecord {
+            p: ray.at(t),
+            front_face,
+            normal,
+            geometric_normal: normal,
+            t,
+            ray_dir: ray.dir,
+            u,
+            v,
+            material,
+            object,
+        }
+    }
+
+    // for surfaces shaded with an interpolated normal
+    pub fn with_geometric_normal(mut self, outward_normal: Vec3) -> Self {
+        self.geometric_normal = if self.front_face { outward_normal } else { -1.0 * outward_normal };
+        self
+    }
+
+    pub fn reflect(&self, fuzz: f64, sampler: &mut dyn Sampler) -> Vec3 {
         let new_dir_offset = Vec3::dot(&self.normal, &self.ray_dir) * self.normal;
-        self.ray_dir - 2.0*new_dir_offset + fuzz * Vec3::random_in_unit()
+        let offset = if fuzz > 0.0 { fuzz * sample_ball(sampler) } else { Vec3::new(0.0, 0.0, 0.0) };
+        self.ray_dir - 2.0*new_dir_offset + offset
     }
 
-    fn schlick(cos: f64, ref_idx: f64) -> bool {
+    fn schlick(cos: f64, ref_idx: f64, sampler: &mut dyn Sampler) -> bool {
         let r0 = ((1.0-ref_idx) / (1.0+ref_idx)).powi(2);
         let reflect_prob = r0 + (1.0-r0)*(1.0 - cos).powi(5);
-        let mut rnd = rand::thread_rng();
-        rnd.gen_range(0.0, 1.0) < reflect_prob
+        sampler.next_1d() < reflect_prob
     }
 
-    pub fn refract_by(&self, refr_ratio: f64) -> Vec3 {
+    pub fn refract_by(&self, refr_ratio: f64, sampler: &mut dyn Sampler) -> Vec3 {
         let refr_ratio = if self.front_face {
             1.0 / refr_ratio
         } else {
@@ -138,10 +359,9 @@ impl HitRecord {
         };
         let cos_theta = -Vec3::dot(&self.ray_dir.unit(), &self.normal);
         let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
-        let reflect_steep = HitRecord::schlick(cos_theta, refr_ratio);
-        if refr_ratio * sin_theta > 1.0 || reflect_steep {
+        if refr_ratio * sin_theta > 1.0 || HitRecord::schlick(cos_theta, refr_ratio, sampler) {
             // reflect instead of refract
-            self.reflect(0.0)
+            self.reflect(0.0, sampler)
         } else {
             let r_out_perp = refr_ratio * (self.ray_dir.unit() + cos_theta * self.normal);
             let r_out_par = -((1.0 - r_out_perp.len_sq()).abs()).sqrt() * self.normal;
@@ -151,78 +371,125 @@ impl HitRecord {
 }
 
 impl Sphere {
-    pub fn new(center: Point, radius: f64, coloring: ColorBehavior) -> Sphere {
-        Sphere{center, radius, coloring}
+    pub fn new(center: Point, radius: f64, material: Arc<dyn Material>) -> Sphere {
+        Sphere{center, motion: Vec3::new(0.0, 0.0, 0.0), radius, material}
+    }
+
+    // sphere moving in a straight line from center0 at time 0 to center1 at time 1
+    pub fn moving(center0: Point, center1: Point, radius: f64, material: Arc<dyn Material>) -> Sphere {
+        Sphere{center: center0, motion: center1 - center0, radius, material}
+    }
+
+    // it stays at its ends outside times 0 to 1, so it never leaves its bounding box
+    pub fn center_at(&self, time: f64) -> Point {
+        self.center + time.clamp(0.0, 1.0) * self.motion
     }
 
-    pub fn hit_at(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let oc = ray.origin - self.center;
+    pub fn hit_at(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let solution = self.solutions(ray)?.iter().copied().find(|t| *t >= t_min && *t <= t_max)?;
+        Some(self.hit_record(ray, solution))
+    }
+
+    // where the ray enters and leaves the sphere
+    fn solutions(&self, ray: &Ray) -> Option<[f64; 2]> {
+        let oc = ray.origin - self.center_at(ray.time);
         let a = Vec3::dot(&ray.dir, &ray.dir);
         let b = 2.0 * Vec3::dot(&ray.dir, &oc);
         let c = Vec3::dot(&oc, &oc) - self.radius * self.radius;
         let discr = b*b - 4.0*a*c;
-        if discr >= 0.0 {
-            let solution = (-b - discr.sqrt()) / (2.0 * a);
-            if solution >= t_min && solution <= t_max {
-                return Some(self.hit_record(ray, solution))
-            }
-            let solution = (-b + discr.sqrt()) / (2.0 * a);
-            if solution >= t_min && solution <= t_max {
-                return Some(self.hit_record(ray, solution))
-            }
+        if discr < 0.0 {
+            return None;
         }
-        None
+        Some([(-b - discr.sqrt()) / (2.0 * a), (-b + discr.sqrt()) / (2.0 * a)])
     }
 
-    fn hit_record(&self, ray: &Ray, t: f64) -> HitRecord {
-        let p = ray.at(t);
-        let outward_normal = (p - self.center).unit();
-        let (normal, front_face) = if Vec3::dot(&outward_normal, &ray.dir) > 0.0 {
-            // ray in the same dir as the outward normal, so it comes from inside
-            (-1.0 * outward_normal, false)
-        } else {
-            // ray in the opposite dir from the outward normal, so it comes from outside
-            (outward_normal, true)
-        };
-        HitRecord {
-            p,
-            front_face,
-            normal,
-            t,
-            ray_dir: ray.dir,
-        }
+    fn hit_record(&self, ray: &Ray, t: f64) -> HitRecord<'_> {
+        let outward_normal = (ray.at(t) - self.center_at(ray.time)).unit();
+        HitRecord::new(ray, t, outward_normal, sphere_uv(outward_normal), &*self.material, self)
     }
 }
 
+// (u, v) of a point on the unit sphere: u goes around the y axis starting at -x,
+// v from the bottom pole to the top one
+fn sphere_uv(p: Vec3) -> (f64, f64) {
+    let theta = (-p.get_y()).clamp(-1.0, 1.0).acos();
+    let phi = (-p.get_z()).atan2(p.get_x()) + PI;
+    (phi / (2.0 * PI), theta / PI)
+}
+
 impl Hittable for Sphere {
-    fn hit(&self, ray: &Ray, scene: &Scene, min_t: f64, max_t: f64, depth: u32) -> Option<(Color, HitRecord)> {
-        self.hit_at(ray, min_t, max_t).map(|hr| {
-            let color = match self.coloring {
-                ColorBehavior::Normal => 0.5 * (hr.normal + 1.0),
-                ColorBehavior::Color(color) => color,
-                ColorBehavior::Diffuse => {
-                    let new_dir = hr.normal + Vec3::random_in_unit();
-                    let ray = Ray::new(hr.p, new_dir);
-                    let color = scene.hit(&ray, depth-1).unwrap_or_else(|| scene.bg_color(&new_dir));
-                    0.5 * color
-                },
-               ColorBehavior::LambertDiffuse(attenuation) => {
-                   let new_dir = hr.normal + Vec3::random_unit();
-                   let ray = Ray::new(hr.p, new_dir);
-                   let color = scene.hit(&ray, depth-1).unwrap_or_else(|| scene.bg_color(&new_dir));
-                   attenuation * color
-               },
-                ColorBehavior::Reflect(attenuation, fuzz) => {
-                    let ray = Ray::new(hr.p, hr.reflect(fuzz));
-                    let color = scene.hit(&ray, depth-1).unwrap_or_else(|| scene.bg_color(&ray.dir));
-                    attenuation * color
-                },
-                ColorBehavior::Dielectric(refract_idx) => {
-                    let ray = Ray::new(hr.p, hr.refract_by(refract_idx));
-                    scene.hit(&ray, depth-1).unwrap_or_else(|| scene.bg_color(&ray.dir))
-                },
-            };
-            (color, hr)
-        })
+    fn hit(&self, ray: &Ray, min_t: f64, max_t: f64) -> Option<HitRecord<'_>> {
+        self.hit_at(ray, min_t, max_t)
+    }
+
+    fn crossings(&self, ray: &Ray, min_t: f64, max_t: f64) -> Vec<HitRecord<'_>> {
+        self.solutions(ray)
+            .into_iter()
+            .flatten()
+            .filter(|t| *t >= min_t && *t <= max_t)
+            .map(|t| self.hit_record(ray, t))
+            .collect()
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Vec3::new(self.radius.abs(), self.radius.abs(), self.radius.abs());
+        let (c0, c1) = (self.center_at(0.0), self.center_at(1.0));
+        Some(Aabb::surrounding(&Aabb::new(c0 - r, c0 + r), &Aabb::new(c1 - r, c1 + r)))
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_light()
+    }
+
+    // uniform over the cone of directions the sphere covers as seen from origin
+    fn sample_direction(&self, origin: Point, time: f64, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        let to_center = self.center_at(time) - origin;
+        let dist_sq = to_center.len_sq();
+        if dist_sq <= self.radius * self.radius {
+            return None;
+        }
+        let cos_max = (1.0 - self.radius * self.radius / dist_sq).sqrt();
+        let (su, sv) = sampler.next_2d();
+        let z = 1.0 + su * (cos_max - 1.0);
+        let phi = 2.0 * PI * sv;
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let w = to_center.unit();
+        let (u, v) = w.basis();
+        Some(r * phi.cos() * u + r * phi.sin() * v + z * w)
+    }
+
+    fn direction_pdf(&self, origin: Point, dir: Vec3, time: f64) -> f64 {
+        let dist_sq = (self.center_at(time) - origin).len_sq();
+        if dist_sq <= self.radius * self.radius || self.hit_at(&Ray::with_time(origin, dir, time), 0.0, f64::INFINITY).is_none() {
+            return 0.0;
+        }
+        let cos_max = (1.0 - self.radius * self.radius / dist_sq).sqrt();
+        1.0 / (2.0 * PI * (1.0 - cos_max))
+    }
+}
+
+#[test]
+fn test_moving_sphere() {
+    let mat = Arc::new(LambertDiffuse::new(Color::new(0.5, 0.5, 0.5)));
+    let sphere = Sphere::moving(Point::new(0.0, 0.0, 0.0), Point::new(2.0, 0.0, 0.0), 0.5, mat);
+    let down_z = |x: f64, time: f64| Ray::with_time(Point::new(x, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0), time);
+
+    assert!(sphere.hit(&down_z(0.0, 0.0), 0.0, f64::INFINITY).is_some());
+    assert!(sphere.hit(&down_z(0.0, 1.0), 0.0, f64::INFINITY).is_none());
+    assert!(sphere.hit(&down_z(2.0, 1.0), 0.0, f64::INFINITY).is_some());
+    // halfway there at time 0.5, the hit point and normal move along
+    let hr = sphere.hit(&down_z(1.3, 0.5), 0.0, f64::INFINITY).unwrap();
+    assert!((hr.normal - Vec3::new(0.3, 0.0, 0.4) / 0.5).len() < 1e-9);
+    assert!((hr.p - Point::new(1.3, 0.0, 0.4)).len() < 1e-9);
+
+    let bbox = sphere.bounding_box().unwrap();
+    assert_eq!((bbox.min, bbox.max), (Point::new(-0.5, -0.5, -0.5), Point::new(2.5, 0.5, 0.5)));
+
+    // past time 1 it stays at its end, where the scene's bvh still finds it
+    let mut scene = Scene::new(0.001, f64::INFINITY);
+    scene.add(Box::new(sphere));
+    let mut sampler = crate::SamplerKind::Random.start(0, 0, 0, 1);
+    let hr = scene.hit(&down_z(2.0, 3.0), &mut *sampler).unwrap();
+    assert!((hr.p - Point::new(2.0, 0.0, 0.5)).len() < 1e-9);
+    assert!(scene.hit(&down_z(4.0, 2.0), &mut *sampler).is_none());
 }
@@ -0,0 +1,1106 @@
+use crate::{Point, Vec3, Color, Focus, Aperture, PathTracer, SamplerKind, Filter, ToneMap, Transfer, Background, Light, Scene, Camera, valid_shutter, Sphere, Triangle, ObjModel, ObjError};
+use crate::{Hittable, Transform, Instance, XYRect, XZRect, YZRect, Quad, Plane, Cuboid, Disk, Cylinder, Cone, Torus, Capsule, Csg, CsgOp, ConstantMedium};
+use crate::{Material, Normal, FlatColor, Diffuse, LambertDiffuse, Reflect, Dielectric, DiffuseLight, HenyeyGreenstein};
+use crate::{Texture, SolidColor, Checker, NoisePattern, NoiseTexture, ImageTexture};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub enum SceneFileError {
+    Io(PathBuf, io::Error),
+    Parse {line: usize, message: String},
+    Obj(ObjError),
+    // a name with nothing defined under it, only possible in descriptions made in code
+    Unknown {kind: &'static str, name: String},
+}
+
+impl fmt::Display for SceneFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneFileError::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            SceneFileError::Parse {line, message} => write!(f, "line {}: {}", line, message),
+            SceneFileError::Obj(err) => write!(f, "{}", err),
+            SceneFileError::Unknown {kind, name} => write!(f, "unknown {} '{}'", kind, name),
+        }
+    }
+}
+
+impl std::error::Error for SceneFileError {}
+
+impl From<ObjError> for SceneFileError {
+    fn from(err: ObjError) -> Self {
+        SceneFileError::Obj(err)
+    }
+}
+
+// Only the settings present in the file are applied, the rest keep the Camera defaults.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CameraDesc {
+    pub position: Option<Point>,
+    pub lookat: Option<Point>,
+    pub up: Option<Vec3>,
+    pub aspect_ratio: Option<f64>,
+    pub vertical_fov: Option<f64>,
+    pub image_width: Option<usize>,
+    pub antialiasing: Option<u32>,
+    pub focus: Option<Focus>,
+    pub aperture: Option<Aperture>,
+    pub blades: Option<u32>,
+    pub shutter: Option<(f64, f64)>,
+    pub max_recursion: Option<u32>,
+    pub russian_roulette: Option<u32>,
+    pub threads: Option<usize>,
+    pub seed: Option<u64>,
+    pub sampler: Option<SamplerKind>,
+    pub filter: Option<Filter>,
+    pub exposure: Option<f64>,
+    pub tone_map: Option<ToneMap>,
+    pub transfer: Option<Transfer>,
+    pub dither: Option<bool>,
+}
+
+impl CameraDesc {
+    pub fn build(&self) -> Camera {
+        let mut cam = Camera::new(
+            self.position.unwrap_or_else(|| Point::new(0.0, 0.0, 0.0)),
+            self.lookat.unwrap_or_else(|| Point::new(0.0, 0.0, -1.0)),
+            self.up.unwrap_or_else(|| Vec3::new(0.0, 1.0, 0.0)),
+        );
+        if let Some(ar) = self.aspect_ratio { cam = cam.aspect_ratio(ar) }
+        if let Some(fov) = self.vertical_fov { cam = cam.vertical_fov(fov) }
+        if let Some(width) = self.image_width { cam = cam.image_width(width) }
+        if let Some(aa) = self.antialiasing { cam = cam.antialiasing(aa) }
+        if let Some(focus) = self.focus { cam = cam.focus(focus) }
+        match self.aperture {
+            Some(Aperture::Radius(radius)) => cam = cam.aperture(2.0 * radius),
+            Some(Aperture::FStop(f_number)) => cam = cam.f_stop(f_number),
+            None => {},
+        }
+        if let Some(blades) = self.blades { cam = cam.blades(blades) }
+        if let Some((open, close)) = self.shutter { cam = cam.shutter(open, close) }
+        if let Some(max_rec) = self.max_recursion { cam = cam.max_recursion(max_rec) }
+        if let Some(bounces) = self.russian_roulette { cam = cam.integrator(PathTracer::new().russian_roulette(bounces)) }
+        if let Some(threads) = self.threads { cam = cam.threads(threads) }
+        if let Some(seed) = self.seed { cam = cam.seed(seed) }
+        if let Some(sampler) = self.sampler { cam = cam.sampler(sampler) }
+        if let Some(filter) = self.filter { cam = cam.filter(filter) }
+        if let Some(exposure) = self.exposure { cam = cam.exposure(exposure) }
+        if let Some(tone_map) = self.tone_map { cam = cam.tone_map(tone_map) }
+        if let Some(transfer) = self.transfer { cam = cam.transfer(transfer) }
+        if let Some(dither) = self.dither { cam = cam.dither(dither) }
+        cam
+    }
+}
+
+// Wherever a texture goes, a plain color or the name of a texture defined earlier.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextureRef {
+    Color(Color),
+    Named(String),
+}
+
+impl TextureRef {
+    fn build(&self, textures: &HashMap<&str, Arc<dyn Texture>>) -> Result<Arc<dyn Texture>, SceneFileError> {
+        match self {
+            TextureRef::Color(c) => Ok(Arc::new(SolidColor(*c))),
+            TextureRef::Named(name) => textures
+                .get(name.as_str())
+                .cloned()
+                .ok_or_else(|| SceneFileError::Unknown {kind: "texture", name: name.clone()}),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextureDesc {
+    Solid(Color),
+    Checker {scale: f64, even: TextureRef, odd: TextureRef},
+    Noise(NoisePattern, f64),
+    Image {path: String},
+}
+
+impl TextureDesc {
+    // image paths are resolved relative to base_dir
+    pub fn build(&self, textures: &HashMap<&str, Arc<dyn Texture>>, base_dir: &Path) -> Result<Arc<dyn Texture>, SceneFileError> {
+        Ok(match self {
+            TextureDesc::Solid(c) => Arc::new(SolidColor(*c)),
+            TextureDesc::Checker {scale, even, odd} => Arc::new(Checker::new(even.build(textures)?, odd.build(textures)?, *scale)),
+            TextureDesc::Noise(pattern, scale) => Arc::new(NoiseTexture::new(*pattern, *scale, 0)),
+            TextureDesc::Image {path} => {
+                let path = base_dir.join(path);
+                Arc::new(ImageTexture::load(&path).map_err(|e| SceneFileError::Io(path, e))?)
+            },
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MaterialDesc {
+    Normal,
+    Color(Color),
+    Diffuse,
+    LambertDiffuse(TextureRef),
+    Reflect(TextureRef, f64),
+    Dielectric(f64),
+    Light(Color),
+    // phase function of media, isotropic when g is 0
+    Phase(TextureRef, f64),
+}
+
+impl MaterialDesc {
+    pub fn build(&self, textures: &HashMap<&str, Arc<dyn Texture>>) -> Result<Arc<dyn Material>, SceneFileError> {
+        Ok(match self {
+            MaterialDesc::Normal => Arc::new(Normal),
+            MaterialDesc::Color(c) => Arc::new(FlatColor(*c)),
+            MaterialDesc::Diffuse => Arc::new(Diffuse),
+            MaterialDesc::LambertDiffuse(tex) => Arc::new(LambertDiffuse::textured(tex.build(textures)?)),
+            MaterialDesc::Reflect(tex, fuzz) => Arc::new(Reflect::textured(tex.build(textures)?, *fuzz)),
+            MaterialDesc::Dielectric(idx) => Arc::new(Dielectric::new(*idx)),
+            MaterialDesc::Light(c) => Arc::new(DiffuseLight::new(*c)),
+            MaterialDesc::Phase(tex, g) => Arc::new(HenyeyGreenstein::textured(tex.build(textures)?, *g)),
+        })
+    }
+}
+
+// Each one is applied to the object first, before the transforms above it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransformDesc {
+    Identity,
+    Translate(Vec3),
+    // degrees around an axis
+    Rotate(f64, Vec3),
+    Scale(Vec3),
+}
+
+impl TransformDesc {
+    pub fn build(&self) -> Transform {
+        match *self {
+            TransformDesc::Identity => Transform::identity(),
+            TransformDesc::Translate(offset) => Transform::translate(offset),
+            TransformDesc::Rotate(degrees, axis) => Transform::rotate(axis, degrees),
+            TransformDesc::Scale(factors) => Transform::scale(factors),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjectDesc {
+    // changes the transform of the objects after it, random spheres ignore it
+    Transform(TransformDesc),
+    Sphere {center: Point, radius: f64, material: String},
+    MovingSphere {centers: [Point; 2], radius: f64, material: String},
+    Triangle {vertices: [Point; 3], material: String},
+    // perpendicular to axis at k, a and b are the ranges along the other two axes
+    Rect {axis: usize, a: (f64, f64), b: (f64, f64), k: f64, material: String},
+    Quad {origin: Point, u: Vec3, v: Vec3, material: String},
+    Plane {point: Point, normal: Vec3, material: String},
+    Cuboid {min: Point, max: Point, material: String},
+    Disk {center: Point, normal: Vec3, radius: f64, material: String},
+    Cylinder {base: Point, top: Point, radius: f64, capped: bool, material: String},
+    Cone {base: Point, apex: Point, radius: f64, capped: bool, material: String},
+    Torus {center: Point, axis: Vec3, major: f64, minor: f64, material: String},
+    Capsule {a: Point, b: Point, radius: f64, material: String},
+    // combines the two objects before it into one, they can't come from obj files or random
+    Csg(CsgOp),
+    // fills the object before it with fog scattering by the material's phase function
+    Medium {density: f64, material: String},
+    Obj {path: String},
+    Random {side_count: u32, seed: u64},
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneDesc {
+    pub camera: CameraDesc,
+    pub min_t: f64,
+    pub max_t: f64,
+    pub background: Option<Background>,
+    pub textures: Vec<(String, TextureDesc)>,
+    pub materials: Vec<(String, MaterialDesc)>,
+    pub lights: Vec<Light>,
+    pub objects: Vec<ObjectDesc>,
+}
+
+impl Default for SceneDesc {
+    fn default() -> Self {
+        SceneDesc {
+            camera: CameraDesc::default(),
+            min_t: 0.001,
+            max_t: f64::INFINITY,
+            background: None,
+            textures: Vec::new(),
+            materials: Vec::new(),
+            lights: Vec::new(),
+            objects: Vec::new(),
+        }
+    }
+}
+
+struct Args<'a> {
+    line: usize,
+    words: Vec<&'a str>,
+    pos: usize,
+}
+
+impl<'a> Args<'a> {
+    fn error(&self, message: String) -> SceneFileError {
+        SceneFileError::Parse {line: self.line, message}
+    }
+
+    fn word(&mut self, what: &str) -> Result<&'a str, SceneFileError> {
+        let word = self.words.get(self.pos).copied().ok_or_else(|| self.error(format!("missing {}", what)))?;
+        self.pos += 1;
+        Ok(word)
+    }
+
+    fn num<T: std::str::FromStr>(&mut self, what: &str) -> Result<T, SceneFileError> {
+        let word = self.word(what)?;
+        // nan and inf would only fail later, in the middle of a render
+        match word.parse() {
+            Ok(x) if word.parse::<f64>().map_or(true, f64::is_finite) => Ok(x),
+            _ => Err(self.error(format!("invalid {} '{}'", what, word))),
+        }
+    }
+
+    fn vec(&mut self, what: &str) -> Result<Vec3, SceneFileError> {
+        Ok(Vec3::new(self.num(what)?, self.num(what)?, self.num(what)?))
+    }
+
+    fn end(&self) -> Result<(), SceneFileError> {
+        match self.words.get(self.pos) {
+            Some(extra) => Err(self.error(format!("unexpected '{}'", extra))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl SceneDesc {
+    pub fn parse(src: &str) -> Result<SceneDesc, SceneFileError> {
+        let mut desc = SceneDesc::default();
+        // objects before this line that csg operators can still combine, and whether
+        // each is a medium, which has no surface for them to work with
+        let mut operands: Vec<bool> = Vec::new();
+
+        for (i, line) in src.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("");
+            let mut args = Args {line: i + 1, words: line.split_whitespace().collect(), pos: 0};
+            if args.words.is_empty() {
+                continue;
+            }
+            let objects = desc.objects.len();
+            match args.word("keyword")? {
+                "camera" => desc.parse_camera(&mut args)?,
+                "clip" => {
+                    desc.min_t = args.num("min t")?;
+                    // the one number that may be infinite, rays going on forever
+                    desc.max_t = if args.words.get(args.pos) == Some(&"inf") {
+                        args.pos += 1;
+                        f64::INFINITY
+                    } else {
+                        args.num("max t")?
+                    };
+                },
+                "background" => {
+                    desc.background = Some(if args.words.get(1) == Some(&"sky") {
+                        args.pos += 1;
+                        Background::Sky
+                    } else {
+                        Background::Color(args.vec("background color")?)
+                    });
+                },
+                "light" => desc.lights.push(parse_light(&mut args)?),
+                "texture" => {
+                    let name = args.word("texture name")?.to_string();
+                    if desc.texture(&name).is_some() {
+                        return Err(args.error(format!("texture '{}' defined twice", name)));
+                    }
+                    let texture = desc.parse_texture(&mut args)?;
+                    desc.textures.push((name, texture));
+                },
+                "material" => {
+                    let name = args.word("material name")?.to_string();
+                    if desc.material(&name).is_some() {
+                        return Err(args.error(format!("material '{}' defined twice", name)));
+                    }
+                    let material = desc.parse_material(&mut args)?;
+                    desc.materials.push((name, material));
+                },
+                "sphere" => {
+                    let center = args.vec("center")?;
+                    let radius = args.num("radius")?;
+                    let material = desc.material_ref(&mut args)?;
+                    desc.objects.push(ObjectDesc::Sphere {center, radius, material});
+                },
+                "moving_sphere" => {
+                    let centers = [args.vec("start center")?, args.vec("end center")?];
+                    let radius = args.num("radius")?;
+                    let material = desc.material_ref(&mut args)?;
+                    desc.objects.push(ObjectDesc::MovingSphere {centers, radius, material});
+                },
+                "triangle" => {
+                    let vertices = [args.vec("vertex")?, args.vec("vertex")?, args.vec("vertex")?];
+                    let material = desc.material_ref(&mut args)?;
+                    desc.objects.push(ObjectDesc::Triangle {vertices, material});
+                },
+                kind @ ("yz_rect" | "xz_rect" | "xy_rect") => {
+                    let axis = ["yz_rect", "xz_rect", "xy_rect"].iter().position(|k| *k == kind).unwrap_or(0);
+                    let a = (args.num("rectangle bound")?, args.num("rectangle bound")?);
+                    let b = (args.num("rectangle bound")?, args.num("rectangle bound")?);
+                    let k = args.num("rectangle position")?;
+                    if a.0 == a.1 || b.0 == b.1 {
+                        return Err(args.error("rectangle bounds can't be equal".to_string()));
+                    }
+                    let material = desc.material_ref(&mut args)?;
+                    desc.objects.push(ObjectDesc::Rect {axis, a, b, k, material});
+                },
+                "quad" => {
+                    let origin = args.vec("corner")?;
+                    let (u, v) = (args.vec("side")?, args.vec("side")?);
+                    if Vec3::cross(&u, &v).len_sq() == 0.0 {
+                        return Err(args.error("quad sides can't be zero or parallel".to_string()));
+                    }
+                    let material = desc.material_ref(&mut args)?;
+                    desc.objects.push(ObjectDesc::Quad {origin, u, v, material});
+                },
+                "plane" => {
+                    let point = args.vec("point")?;
+                    let normal = args.vec("normal")?;
+                    let material = desc.material_ref(&mut args)?;
+                    desc.objects.push(ObjectDesc::Plane {point, normal, material});
+                },
+                "box" => {
+                    let (min, max) = (args.vec("corner")?, args.vec("corner")?);
+                    if (0..3).any(|axis| min[axis] == max[axis]) {
+                        return Err(args.error("box corners can't share a coordinate".to_string()));
+                    }
+                    let material = desc.material_ref(&mut args)?;
+                    desc.objects.push(ObjectDesc::Cuboid {min, max, material});
+                },
+                "disk" => {
+                    let (center, normal) = (args.vec("center")?, args.vec("normal")?);
+                    if normal.len_sq() == 0.0 {
+                        return Err(args.error("disk normal can't be zero".to_string()));
+                    }
+                    let radius = args.num("radius")?;
+                    let material = desc.material_ref(&mut args)?;
+                    desc.objects.push(ObjectDesc::Disk {center, normal, radius, material});
+                },
+                kind @ ("cylinder" | "cone") => {
+                    let (base, top) = (args.vec("base center")?, args.vec("end point")?);
+                    if (top - base).len_sq() == 0.0 {
+                        return Err(args.error(format!("{} base and end point can't be the same", kind)));
+                    }
+                    let radius = args.num("radius")?;
+                    let material = desc.material_ref(&mut args)?;
+                    let capped = args.words.get(args.pos) != Some(&"open");
+                    if !capped {
+                        args.pos += 1;
+                    }
+                    desc.objects.push(if kind == "cylinder" {
+                        ObjectDesc::Cylinder {base, top, radius, capped, material}
+                    } else {
+                        ObjectDesc::Cone {base, apex: top, radius, capped, material}
+                    });
+                },
+                "torus" => {
+                    let (center, axis) = (args.vec("center")?, args.vec("axis")?);
+                    if axis.len_sq() == 0.0 {
+                        return Err(args.error("torus axis can't be zero".to_string()));
+                    }
+                    let (major, minor) = (args.num("major radius")?, args.num("minor radius")?);
+                    let material = desc.material_ref(&mut args)?;
+                    desc.objects.push(ObjectDesc::Torus {center, axis, major, minor, material});
+                },
+                "capsule" => {
+                    let (a, b) = (args.vec("end point")?, args.vec("end point")?);
+                    if (b - a).len_sq() == 0.0 {
+                        return Err(args.error("capsule end points can't be the same, use a sphere".to_string()));
+                    }
+                    let radius = args.num("radius")?;
+                    let material = desc.material_ref(&mut args)?;
+                    desc.objects.push(ObjectDesc::Capsule {a, b, radius, material});
+                },
+                "obj" => desc.objects.push(ObjectDesc::Obj {path: args.word("path")?.to_string()}),
+                "identity" => desc.objects.push(ObjectDesc::Transform(TransformDesc::Identity)),
+                "translate" => desc.objects.push(ObjectDesc::Transform(TransformDesc::Translate(args.vec("offset")?))),
+                "rotate" => {
+                    let degrees = args.num("rotation angle")?;
+                    let axis = args.vec("rotation axis")?;
+                    if axis.len_sq() == 0.0 {
+                        return Err(args.error("rotation axis can't be zero".to_string()));
+                    }
+                    desc.objects.push(ObjectDesc::Transform(TransformDesc::Rotate(degrees, axis)));
+                },
+                "scale" => {
+                    let factors = args.vec("scale")?;
+                    if (0..3).any(|axis| factors[axis] == 0.0) {
+                        return Err(args.error("scale factors can't be zero".to_string()));
+                    }
+                    desc.objects.push(ObjectDesc::Transform(TransformDesc::Scale(factors)));
+                },
+                op @ ("union" | "intersection" | "difference") => {
+                    if operands.len() < 2 {
+                        return Err(args.error(format!("{} needs two objects before it", op)));
+                    }
+                    if operands[operands.len() - 2..].contains(&true) {
+                        return Err(args.error(format!("{} can't be used on a medium", op)));
+                    }
+                    desc.objects.push(ObjectDesc::Csg(CsgOp::from_name(op).unwrap_or(CsgOp::Union)));
+                },
+                "medium" => {
+                    match operands.last() {
+                        None => return Err(args.error("medium needs an object before it".to_string())),
+                        Some(true) => return Err(args.error("a medium can't be the boundary of another".to_string())),
+                        Some(false) => {},
+                    }
+                    let density: f64 = args.num("density")?;
+                    if density <= 0.0 {
+                        return Err(args.error("density must be positive".to_string()));
+                    }
+                    let material = desc.material_ref(&mut args)?;
+                    desc.objects.push(ObjectDesc::Medium {density, material});
+                },
+                "random" => {
+                    let side_count = args.num("side count")?;
+                    let seed = if args.words.len() > args.pos { args.num("seed")? } else { 0 };
+                    desc.objects.push(ObjectDesc::Random {side_count, seed});
+                },
+                keyword => return Err(args.error(format!("unknown keyword '{}'", keyword))),
+            }
+            args.end()?;
+            if let Some(obj) = desc.objects.get(objects) {
+                match obj {
+                    ObjectDesc::Transform(_) => {},
+                    ObjectDesc::Obj {..} | ObjectDesc::Random {..} => operands.clear(),
+                    ObjectDesc::Csg(_) => {
+                        operands.pop();
+                    },
+                    ObjectDesc::Medium {..} => *operands.last_mut().unwrap() = true,
+                    _ => operands.push(false),
+                }
+            }
+        }
+        Ok(desc)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<SceneDesc, SceneFileError> {
+        let path = path.as_ref();
+        let src = fs::read_to_string(path).map_err(|e| SceneFileError::Io(path.to_path_buf(), e))?;
+        SceneDesc::parse(&src)
+    }
+
+    fn parse_camera(&mut self, args: &mut Args) -> Result<(), SceneFileError> {
+        let cam = &mut self.camera;
+        match args.word("camera setting")? {
+            "position" => cam.position = Some(args.vec("position")?),
+            "lookat" => cam.lookat = Some(args.vec("lookat")?),
+            "up" => cam.up = Some(args.vec("up")?),
+            "aspect_ratio" => cam.aspect_ratio = Some(args.num("aspect ratio")?),
+            "vertical_fov" => cam.vertical_fov = Some(args.num("vertical fov")?),
+            "image_width" => cam.image_width = Some(args.num("image width")?),
+            "antialiasing" => cam.antialiasing = Some(args.num("antialiasing")?),
+            // focal_length is what older files called the focus distance
+            "focus_distance" | "focal_length" => {
+                cam.focus = Some(if args.words.get(args.pos) == Some(&"lookat") {
+                    args.pos += 1;
+                    Focus::Lookat
+                } else {
+                    Focus::Distance(args.num("focus distance")?)
+                });
+            },
+            "aperture" => cam.aperture = Some(Aperture::Radius(args.num::<f64>("aperture")? / 2.0)),
+            "f_stop" => cam.aperture = Some(Aperture::FStop(args.num("f-stop")?)),
+            "blades" => cam.blades = Some(args.num("blades")?),
+            "shutter" => {
+                let (open, close) = (args.num("shutter open")?, args.num("shutter close")?);
+                if !valid_shutter(open, close) {
+                    return Err(args.error("shutter times must be 0 <= open <= close <= 1".to_string()));
+                }
+                cam.shutter = Some((open, close));
+            },
+            "max_recursion" => cam.max_recursion = Some(args.num("max recursion")?),
+            "russian_roulette" => cam.russian_roulette = Some(args.num("russian roulette bounces")?),
+            "threads" => cam.threads = Some(args.num("threads")?),
+            "seed" => cam.seed = Some(args.num("seed")?),
+            "sampler" => {
+                let name = args.word("sampler")?;
+                let sampler = SamplerKind::from_name(name).ok_or_else(|| args.error(format!("unknown sampler '{}'", name)))?;
+                cam.sampler = Some(sampler);
+            },
+            "filter" => {
+                let name = args.word("filter")?;
+                let filter = Filter::from_name(name).ok_or_else(|| args.error(format!("unknown filter '{}'", name)))?;
+                cam.filter = Some(filter);
+            },
+            "exposure" => cam.exposure = Some(args.num("exposure")?),
+            "tone_map" => {
+                let name = args.word("tone map")?;
+                let tone_map = ToneMap::from_name(name).ok_or_else(|| args.error(format!("unknown tone map '{}'", name)))?;
+                cam.tone_map = Some(tone_map);
+            },
+            "transfer" => {
+                let name = args.word("transfer function")?;
+                let transfer = Transfer::from_name(name).ok_or_else(|| args.error(format!("unknown transfer function '{}'", name)))?;
+                cam.transfer = Some(transfer);
+            },
+            "dither" => {
+                cam.dither = Some(match args.word("on or off")? {
+                    "on" => true,
+                    "off" => false,
+                    word => return Err(args.error(format!("dither is on or off, not '{}'", word))),
+                });
+            },
+            setting => return Err(args.error(format!("unknown camera setting '{}'", setting))),
+        }
+        Ok(())
+    }
+
+    fn parse_texture(&self, args: &mut Args) -> Result<TextureDesc, SceneFileError> {
+        Ok(match args.word("texture kind")? {
+            "solid" => TextureDesc::Solid(args.vec("color")?),
+            "checker" => TextureDesc::Checker {
+                scale: args.num("checker scale")?,
+                even: self.texture_ref(args)?,
+                odd: self.texture_ref(args)?,
+            },
+            "noise" => TextureDesc::Noise(NoisePattern::Noise, args.num("noise scale")?),
+            "turbulence" => TextureDesc::Noise(NoisePattern::Turbulence, args.num("noise scale")?),
+            "marble" => TextureDesc::Noise(NoisePattern::Marble, args.num("noise scale")?),
+            "image" => TextureDesc::Image {path: args.word("image path")?.to_string()},
+            kind => return Err(args.error(format!("unknown texture kind '{}'", kind))),
+        })
+    }
+
+    fn parse_material(&self, args: &mut Args) -> Result<MaterialDesc, SceneFileError> {
+        Ok(match args.word("material kind")? {
+            "normal" => MaterialDesc::Normal,
+            "color" => MaterialDesc::Color(args.vec("color")?),
+            "diffuse" => MaterialDesc::Diffuse,
+            "lambert" => MaterialDesc::LambertDiffuse(self.texture_ref(args)?),
+            "reflect" => MaterialDesc::Reflect(self.texture_ref(args)?, args.num("fuzz")?),
+            "dielectric" => MaterialDesc::Dielectric(args.num("refraction index")?),
+            "light" => MaterialDesc::Light(args.vec("emitted color")?),
+            "isotropic" => MaterialDesc::Phase(self.texture_ref(args)?, 0.0),
+            "henyey_greenstein" => {
+                let albedo = self.texture_ref(args)?;
+                let g: f64 = args.num("asymmetry")?;
+                if g.abs() >= 1.0 {
+                    return Err(args.error("asymmetry must be between -1 and 1".to_string()));
+                }
+                MaterialDesc::Phase(albedo, g)
+            },
+            kind => return Err(args.error(format!("unknown material kind '{}'", kind))),
+        })
+    }
+
+    fn texture(&self, name: &str) -> Option<&TextureDesc> {
+        self.textures.iter().find(|(n, _)| n == name).map(|(_, t)| t)
+    }
+
+    // a color when it starts with a number, a texture name otherwise
+    fn texture_ref(&self, args: &mut Args) -> Result<TextureRef, SceneFileError> {
+        let word = args.words.get(args.pos).copied().unwrap_or("");
+        if word.parse::<f64>().is_ok() {
+            return Ok(TextureRef::Color(args.vec("color")?));
+        }
+        let name = args.word("color or texture name")?;
+        if self.texture(name).is_none() {
+            return Err(args.error(format!("unknown texture '{}'", name)));
+        }
+        Ok(TextureRef::Named(name.to_string()))
+    }
+
+    fn material(&self, name: &str) -> Option<&MaterialDesc> {
+        self.materials.iter().find(|(n, _)| n == name).map(|(_, m)| m)
+    }
+
+    fn material_ref(&self, args: &mut Args) -> Result<String, SceneFileError> {
+        let name = args.word("material name")?;
+        if self.material(name).is_none() {
+            return Err(args.error(format!("unknown material '{}'", name)));
+        }
+        Ok(name.to_string())
+    }
+
+    // obj paths are resolved relative to base_dir, normally the scene file's directory
+    pub fn build(&self, base_dir: &Path) -> Result<(Scene, Camera), SceneFileError> {
+        let mut scene = Scene::new(self.min_t, self.max_t);
+        if let Some(background) = self.background {
+            scene.set_background(background);
+        }
+        for light in &self.lights {
+            scene.add_light(*light);
+        }
+        // every object using a material or texture name shares one instance of it
+        let mut textures: HashMap<&str, Arc<dyn Texture>> = HashMap::new();
+        for (name, desc) in &self.textures {
+            let texture = desc.build(&textures, base_dir)?;
+            textures.insert(name, texture);
+        }
+        let materials = self
+            .materials
+            .iter()
+            .map(|(name, desc)| Ok((name.as_str(), desc.build(&textures)?)))
+            .collect::<Result<HashMap<&str, Arc<dyn Material>>, SceneFileError>>()?;
+        let material = |name: &str| {
+            materials.get(name).cloned().ok_or_else(|| SceneFileError::Unknown {kind: "material", name: name.to_string()})
+        };
+
+        let mut transform = Transform::identity();
+        // objects wait on a stack for csg operators to combine them before going in the scene
+        let mut placed: Vec<Arc<dyn Hittable>> = Vec::new();
+        let place = |placed: &mut Vec<Arc<dyn Hittable>>, obj: Arc<dyn Hittable>, transform: &Transform| {
+            if transform.is_identity() {
+                placed.push(obj);
+            } else {
+                placed.push(Arc::new(Instance::new(obj, *transform)));
+            }
+        };
+        // an obj file used several times is loaded once and instanced
+        let mut models: HashMap<&str, Vec<Arc<dyn Hittable>>> = HashMap::new();
+        for obj in &self.objects {
+            match obj {
+                ObjectDesc::Transform(TransformDesc::Identity) => transform = Transform::identity(),
+                ObjectDesc::Transform(t) => transform = t.build().then(&transform),
+                ObjectDesc::Sphere {center, radius, material: mat} => {
+                    place(&mut placed, Arc::new(Sphere::new(*center, *radius, material(mat)?)), &transform);
+                },
+                ObjectDesc::MovingSphere {centers: [c0, c1], radius, material: mat} => {
+                    place(&mut placed, Arc::new(Sphere::moving(*c0, *c1, *radius, material(mat)?)), &transform);
+                },
+                ObjectDesc::Triangle {vertices: [a, b, c], material: mat} => {
+                    place(&mut placed, Arc::new(Triangle::new(*a, *b, *c, material(mat)?)), &transform);
+                },
+                ObjectDesc::Rect {axis, a, b, k, material: mat} => {
+                    let rect: Arc<dyn Hittable> = match axis {
+                        0 => Arc::new(YZRect::new(*a, *b, *k, material(mat)?)),
+                        1 => Arc::new(XZRect::new(*a, *b, *k, material(mat)?)),
+                        _ => Arc::new(XYRect::new(*a, *b, *k, material(mat)?)),
+                    };
+                    place(&mut placed, rect, &transform);
+                },
+                ObjectDesc::Quad {origin, u, v, material: mat} => {
+                    place(&mut placed, Arc::new(Quad::new(*origin, *u, *v, material(mat)?)), &transform);
+                },
+                ObjectDesc::Plane {point, normal, material: mat} => {
+                    place(&mut placed, Arc::new(Plane::new(*point, *normal, material(mat)?)), &transform);
+                },
+                ObjectDesc::Cuboid {min, max, material: mat} => {
+                    place(&mut placed, Arc::new(Cuboid::new(*min, *max, material(mat)?)), &transform);
+                },
+                ObjectDesc::Disk {center, normal, radius, material: mat} => {
+                    place(&mut placed, Arc::new(Disk::new(*center, *normal, *radius, material(mat)?)), &transform);
+                },
+                ObjectDesc::Cylinder {base, top, radius, capped, material: mat} => {
+                    let cylinder = Cylinder::new(*base, *top, *radius, material(mat)?);
+                    place(&mut placed, Arc::new(if *capped { cylinder } else { cylinder.uncapped() }), &transform);
+                },
+                ObjectDesc::Cone {base, apex, radius, capped, material: mat} => {
+                    let cone = Cone::new(*base, *apex, *radius, material(mat)?);
+                    place(&mut placed, Arc::new(if *capped { cone } else { cone.uncapped() }), &transform);
+                },
+                ObjectDesc::Torus {center, axis, major, minor, material: mat} => {
+                    place(&mut placed, Arc::new(Torus::new(*center, *axis, *major, *minor, material(mat)?)), &transform);
+                },
+                ObjectDesc::Capsule {a, b, radius, material: mat} => {
+                    place(&mut placed, Arc::new(Capsule::new(*a, *b, *radius, material(mat)?)), &transform);
+                },
+                ObjectDesc::Obj {path} => {
+                    if !models.contains_key(path.as_str()) {
+                        let meshes = ObjModel::load(base_dir.join(path))?.into_meshes();
+                        models.insert(path, meshes.into_iter().map(|m| Arc::new(m) as Arc<dyn Hittable>).collect());
+                    }
+                    for mesh in &models[path.as_str()] {
+                        place(&mut placed, mesh.clone(), &transform);
+                    }
+                },
+                ObjectDesc::Csg(op) if placed.len() >= 2 => {
+                    let (b, a) = (placed.pop().unwrap(), placed.pop().unwrap());
+                    placed.push(Arc::new(Csg::new(*op, a, b)));
+                },
+                // parse doesn't allow it, but descriptions made in code may have nothing to combine
+                ObjectDesc::Csg(_) => {},
+                ObjectDesc::Medium {density, material: mat} => {
+                    if let Some(boundary) = placed.pop() {
+                        placed.push(Arc::new(ConstantMedium::new(boundary, *density, material(mat)?)));
+                    }
+                },
+                ObjectDesc::Random {side_count, seed} => {
+                    placed.drain(..).for_each(|obj| scene.add_shared(obj));
+                    scene.fill_random(*side_count, *seed);
+                },
+            }
+        }
+        placed.into_iter().for_each(|obj| scene.add_shared(obj));
+        Ok((scene, self.camera.build()))
+    }
+}
+
+fn parse_light(args: &mut Args) -> Result<Light, SceneFileError> {
+    Ok(match args.word("light kind")? {
+        "point" => Light::Point {position: args.vec("position")?, intensity: args.vec("intensity")?},
+        "spot" => Light::Spot {
+            position: args.vec("position")?,
+            direction: args.vec("direction")?,
+            intensity: args.vec("intensity")?,
+            inner_angle: args.num("inner angle")?,
+            outer_angle: args.num("outer angle")?,
+        },
+        "directional" => Light::Directional {direction: args.vec("direction")?, irradiance: args.vec("irradiance")?},
+        kind => return Err(args.error(format!("unknown light kind '{}'", kind))),
+    })
+}
+
+struct V(Vec3);
+
+impl fmt::Display for V {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {}", self.0.get_x(), self.0.get_y(), self.0.get_z())
+    }
+}
+
+impl fmt::Display for TextureRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextureRef::Color(c) => write!(f, "{}", V(*c)),
+            TextureRef::Named(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+fn write_texture(f: &mut fmt::Formatter, texture: &TextureDesc) -> fmt::Result {
+    match texture {
+        TextureDesc::Solid(c) => write!(f, "solid {}", V(*c)),
+        TextureDesc::Checker {scale, even, odd} => write!(f, "checker {} {} {}", scale, even, odd),
+        TextureDesc::Noise(pattern, scale) => {
+            let kind = match pattern {
+                NoisePattern::Noise => "noise",
+                NoisePattern::Turbulence => "turbulence",
+                NoisePattern::Marble => "marble",
+            };
+            write!(f, "{} {}", kind, scale)
+        },
+        TextureDesc::Image {path} => write!(f, "image {}", path),
+    }
+}
+
+fn write_material(f: &mut fmt::Formatter, material: &MaterialDesc) -> fmt::Result {
+    match material {
+        MaterialDesc::Normal => write!(f, "normal"),
+        MaterialDesc::Color(c) => write!(f, "color {}", V(*c)),
+        MaterialDesc::Diffuse => write!(f, "diffuse"),
+        MaterialDesc::LambertDiffuse(tex) => write!(f, "lambert {}", tex),
+        MaterialDesc::Reflect(tex, fuzz) => write!(f, "reflect {} {}", tex, fuzz),
+        MaterialDesc::Dielectric(idx) => write!(f, "dielectric {}", idx),
+        MaterialDesc::Light(c) => write!(f, "light {}", V(*c)),
+        MaterialDesc::Phase(tex, g) if *g == 0.0 => write!(f, "isotropic {}", tex),
+        MaterialDesc::Phase(tex, g) => write!(f, "henyey_greenstein {} {}", tex, g),
+    }
+}
+
+// Writes the description back in the format parse reads.
+impl fmt::Display for SceneDesc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let cam = &self.camera;
+        let vecs = [("position", cam.position), ("lookat", cam.lookat), ("up", cam.up)];
+        for (key, v) in vecs.iter() {
+            if let Some(v) = v {
+                writeln!(f, "camera {} {}", key, V(*v))?;
+            }
+        }
+        let floats = [
+            ("aspect_ratio", cam.aspect_ratio),
+            ("vertical_fov", cam.vertical_fov),
+            ("aperture", match cam.aperture {
+                Some(Aperture::Radius(radius)) => Some(2.0 * radius),
+                _ => None,
+            }),
+            ("f_stop", match cam.aperture {
+                Some(Aperture::FStop(f_number)) => Some(f_number),
+                _ => None,
+            }),
+            ("exposure", cam.exposure),
+        ];
+        for (key, x) in floats.iter() {
+            if let Some(x) = x {
+                writeln!(f, "camera {} {}", key, x)?;
+            }
+        }
+        let ints = [
+            ("image_width", cam.image_width),
+            ("antialiasing", cam.antialiasing.map(|x| x as usize)),
+            ("max_recursion", cam.max_recursion.map(|x| x as usize)),
+            ("russian_roulette", cam.russian_roulette.map(|x| x as usize)),
+            ("threads", cam.threads),
+            ("blades", cam.blades.map(|x| x as usize)),
+        ];
+        for (key, x) in ints.iter() {
+            if let Some(x) = x {
+                writeln!(f, "camera {} {}", key, x)?;
+            }
+        }
+        match cam.focus {
+            Some(Focus::Distance(dist)) => writeln!(f, "camera focus_distance {}", dist)?,
+            Some(Focus::Lookat) => writeln!(f, "camera focus_distance lookat")?,
+            None => {},
+        }
+        if let Some((open, close)) = cam.shutter {
+            writeln!(f, "camera shutter {} {}", open, close)?;
+        }
+        if let Some(seed) = cam.seed {
+            writeln!(f, "camera seed {}", seed)?;
+        }
+        if let Some(sampler) = cam.sampler {
+            writeln!(f, "camera sampler {}", sampler.name())?;
+        }
+        if let Some(filter) = cam.filter {
+            writeln!(f, "camera filter {}", filter.name())?;
+        }
+        if let Some(tone_map) = cam.tone_map {
+            writeln!(f, "camera tone_map {}", tone_map.name())?;
+        }
+        if let Some(transfer) = cam.transfer {
+            writeln!(f, "camera transfer {}", transfer.name())?;
+        }
+        if let Some(dither) = cam.dither {
+            writeln!(f, "camera dither {}", if dither { "on" } else { "off" })?;
+        }
+        writeln!(f, "clip {} {}", self.min_t, self.max_t)?;
+
+        match self.background {
+            Some(Background::Sky) => writeln!(f, "background sky")?,
+            Some(Background::Color(c)) => writeln!(f, "background {}", V(c))?,
+            None => {},
+        }
+        for light in &self.lights {
+            match *light {
+                Light::Point {position, intensity} => writeln!(f, "light point {} {}", V(position), V(intensity))?,
+                Light::Spot {position, direction, intensity, inner_angle, outer_angle} => writeln!(
+                    f,
+                    "light spot {} {} {} {} {}",
+                    V(position), V(direction), V(intensity), inner_angle, outer_angle,
+                )?,
+                Light::Directional {direction, irradiance} => {
+                    writeln!(f, "light directional {} {}", V(direction), V(irradiance))?
+                },
+            }
+        }
+        for (name, texture) in &self.textures {
+            write!(f, "texture {} ", name)?;
+            write_texture(f, texture)?;
+            writeln!(f)?;
+        }
+        for (name, material) in &self.materials {
+            write!(f, "material {} ", name)?;
+            write_material(f, material)?;
+            writeln!(f)?;
+        }
+        for obj in &self.objects {
+            match obj {
+                ObjectDesc::Sphere {center, radius, material} => {
+                    writeln!(f, "sphere {} {} {}", V(*center), radius, material)?
+                },
+                ObjectDesc::MovingSphere {centers: [c0, c1], radius, material} => {
+                    writeln!(f, "moving_sphere {} {} {} {}", V(*c0), V(*c1), radius, material)?
+                },
+                ObjectDesc::Triangle {vertices: [a, b, c], material} => {
+                    writeln!(f, "triangle {} {} {} {}", V(*a), V(*b), V(*c), material)?
+                },
+                ObjectDesc::Rect {axis, a, b, k, material} => {
+                    let kind = ["yz_rect", "xz_rect", "xy_rect"][*axis];
+                    writeln!(f, "{} {} {} {} {} {} {}", kind, a.0, a.1, b.0, b.1, k, material)?
+                },
+                ObjectDesc::Quad {origin, u, v, material} => {
+                    writeln!(f, "quad {} {} {} {}", V(*origin), V(*u), V(*v), material)?
+                },
+                ObjectDesc::Plane {point, normal, material} => {
+                    writeln!(f, "plane {} {} {}", V(*point), V(*normal), material)?
+                },
+                ObjectDesc::Cuboid {min, max, material} => writeln!(f, "box {} {} {}", V(*min), V(*max), material)?,
+                ObjectDesc::Disk {center, normal, radius, material} => {
+                    writeln!(f, "disk {} {} {} {}", V(*center), V(*normal), radius, material)?
+                },
+                ObjectDesc::Cylinder {base, top, radius, capped, material} => {
+                    let open = if *capped { "" } else { " open" };
+                    writeln!(f, "cylinder {} {} {} {}{}", V(*base), V(*top), radius, material, open)?
+                },
+                ObjectDesc::Cone {base, apex, radius, capped, material} => {
+                    let open = if *capped { "" } else { " open" };
+                    writeln!(f, "cone {} {} {} {}{}", V(*base), V(*apex), radius, material, open)?
+                },
+                ObjectDesc::Torus {center, axis, major, minor, material} => {
+                    writeln!(f, "torus {} {} {} {} {}", V(*center), V(*axis), major, minor, material)?
+                },
+                ObjectDesc::Capsule {a, b, radius, material} => {
+                    writeln!(f, "capsule {} {} {} {}", V(*a), V(*b), radius, material)?
+                },
+                ObjectDesc::Transform(TransformDesc::Identity) => writeln!(f, "identity")?,
+                ObjectDesc::Transform(TransformDesc::Translate(offset)) => writeln!(f, "translate {}", V(*offset))?,
+                ObjectDesc::Transform(TransformDesc::Rotate(degrees, axis)) => writeln!(f, "rotate {} {}", degrees, V(*axis))?,
+                ObjectDesc::Transform(TransformDesc::Scale(factors)) => writeln!(f, "scale {}", V(*factors))?,
+                ObjectDesc::Csg(op) => writeln!(f, "{}", op.name())?,
+                ObjectDesc::Medium {density, material} => writeln!(f, "medium {} {}", density, material)?,
+                ObjectDesc::Obj {path} => writeln!(f, "obj {}", path)?,
+                ObjectDesc::Random {side_count, seed} => writeln!(f, "random {} {}", side_count, seed)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_scene_file_round_trip() {
+    let src = "camera position 13 2 3\n\
+               camera vertical_fov 20  # degrees\n\
+               camera antialiasing 100\n\
+               camera seed 7\n\
+               camera sampler sobol\n\
+               camera focus_distance lookat\n\
+               camera f_stop 2.8\n\
+               camera blades 6\n\
+               camera shutter 0 0.5\n\
+               camera filter gaussian:2\n\
+               camera tone_map reinhard:4\n\
+               camera transfer gamma:2.2\n\
+               camera dither off\n\
+               background 0 0 0\n\
+               light point 0 5 0 10 10 10\n\
+               light spot 0 5 0 0 -1 0 10 10 10 20 30\n\
+               texture stone marble 4\n\
+               texture tiles checker 10 0.9 0.9 0.9 stone\n\
+               texture earth image earth.png\n\
+               material lamp light 4 4 4\n\
+               material ground lambert tiles\n\
+               material globe lambert earth\n\
+               material glass dielectric 1.5\n\
+               material metal reflect 0.7 0.6 0.5 0.1\n\
+               material rusty reflect stone 0.3\n\
+               material fog isotropic 0.9 0.9 0.9\n\
+               material smoke henyey_greenstein stone -0.3\n\
+               sphere 0 -1000 0 1000 ground\n\
+               sphere 0 1 0 1 glass\n\
+               moving_sphere 2 1 0 2 1.5 0 0.5 metal\n\
+               triangle 0 0 0 1 0 0 0 1 0 metal\n\
+               translate 0 1 0\n\
+               rotate 30 0 1 0\n\
+               scale 2 1 1\n\
+               triangle 0 0 0 1 0 0 0 1 0 metal\n\
+               identity\n\
+               xz_rect 213 343 227 332 554 lamp\n\
+               yz_rect 0 555 0 555 0 ground\n\
+               quad 0 0 0 1 0 0 0 0 1 ground\n\
+               plane 0 -2 0 0 1 0 ground\n\
+               box 0 0 0 1 2 3 metal\n\
+               disk 0 3 0 0 -1 0 0.5 lamp\n\
+               cylinder 0 0 0 0 1 0 0.5 metal open\n\
+               cone 2 0 0 2 1 0 0.5 glass\n\
+               torus 0 0 2 0 1 0 1 0.2 metal\n\
+               capsule -2 0 0 -2 1 0 0.3 ground\n\
+               box -1 -1 -1 1 1 1 metal\n\
+               translate 0 1 0\n\
+               sphere 0 0 0 1.2 metal\n\
+               identity\n\
+               sphere 0 0 1 0.5 glass\n\
+               union\n\
+               difference\n\
+               sphere 0 1 0 1 glass\n\
+               medium 0.5 fog\n\
+               box 0 0 0 1 1 1 glass\n\
+               medium 2 smoke\n\
+               random 4 12\n";
+    let desc = SceneDesc::parse(src).unwrap();
+    assert_eq!(desc.camera.vertical_fov, Some(20.0));
+    assert_eq!(desc.objects.len(), 31);
+    assert_eq!(desc.textures.len(), 3);
+    assert_eq!((desc.camera.transfer, desc.camera.dither), (Some(Transfer::Gamma(2.2)), Some(false)));
+    assert_eq!(SceneDesc::parse(&desc.to_string()).unwrap(), desc);
+    assert!(SceneDesc::parse("camera transfer gamma:0\n").is_err());
+    assert!(SceneDesc::parse("camera dither maybe\n").is_err());
+
+    // descriptions made in code can name things that were never defined
+    let mut dangling = SceneDesc::parse("material red lambert 1 0 0\n").unwrap();
+    dangling.objects.push(ObjectDesc::Sphere {center: Point::new(0.0, 0.0, 0.0), radius: 1.0, material: "blue".to_string()});
+    assert!(matches!(dangling.build(Path::new("")), Err(SceneFileError::Unknown {kind: "material", ..})));
+    let mut dangling = SceneDesc::default();
+    dangling.materials.push(("wood".to_string(), MaterialDesc::LambertDiffuse(TextureRef::Named("oak".to_string()))));
+    assert!(matches!(dangling.build(Path::new("")), Err(SceneFileError::Unknown {kind: "texture", ..})));
+
+    match SceneDesc::parse("material red lambert 1 0 0\nsphere 0 0 0 1 blue\n") {
+        Err(SceneFileError::Parse {line, message}) => {
+            assert_eq!(line, 2);
+            assert_eq!(message, "unknown material 'blue'");
+        },
+        _ => panic!("expected a parse error"),
+    }
+    assert!(SceneDesc::parse("material wood lambert oak\n").is_err());
+    match SceneDesc::parse("material red lambert 1 0 0\nsphere nan 0 0 1 red\n") {
+        Err(SceneFileError::Parse {line, message}) => {
+            assert_eq!(line, 2);
+            assert_eq!(message, "invalid center 'nan'");
+        },
+        _ => panic!("expected a parse error"),
+    }
+    assert!(SceneDesc::parse("material red lambert 1 0 0\nsphere 0 0 0 inf red\n").is_err());
+    for degenerate in [
+        "disk 0 0 0 0 0 0 1 red",
+        "cylinder 1 2 3 1 2 3 1 red",
+        "cone 0 0 0 0 0 0 1 red open",
+        "torus 0 0 0 0 0 0 1 0.2 red",
+        "capsule 1 1 1 1 1 1 0.5 red",
+        "xy_rect 0 1 2 2 0 red",
+        "quad 0 0 0 1 0 0 0 0 0 red",
+        "quad 0 0 0 1 2 3 -2 -4 -6 red",
+        "box 0 0 0 1 0 1 red",
+    ].iter() {
+        let src = format!("material red lambert 1 0 0\n{}\n", degenerate);
+        assert!(matches!(SceneDesc::parse(&src), Err(SceneFileError::Parse {line: 2, ..})), "{}", degenerate);
+    }
+    assert!(SceneDesc::parse("material red lambert 1 0 0\nsphere 0 0 0 1 red\nobj a.obj\nsphere 0 0 0 1 red\nunion\n").is_err());
+    assert!(SceneDesc::parse("material fog isotropic 1 1 1\nmedium 1 fog\n").is_err());
+    let fog = "material fog isotropic 1 1 1\nsphere 0 0 0 1 fog\nmedium 1 fog\n";
+    for after in ["sphere 0 0 0 0.5 fog\nunion", "sphere 0 0 0 0.5 fog\ndifference", "medium 2 fog"].iter() {
+        assert!(SceneDesc::parse(&format!("{}{}\n", fog, after)).is_err(), "{}", after);
+    }
+    assert!(SceneDesc::parse(&format!("{}sphere 0 0 0 0.5 fog\nsphere 0 0 0 0.7 fog\nunion\n", fog)).is_ok());
+    for density in ["0", "-1"].iter() {
+        let src = format!("material fog isotropic 1 1 1\nsphere 0 0 0 1 fog\nmedium {} fog\n", density);
+        assert!(SceneDesc::parse(&src).is_err(), "{}", density);
+    }
+    assert!(SceneDesc::parse("material fog henyey_greenstein 1 1 1 1\n").is_err());
+    assert!(SceneDesc::parse("camera shutter 0.5 2\n").is_err());
+    assert!(SceneDesc::parse("camera shutter -0.5 0.5\n").is_err());
+}
+
+#[test]
+fn test_scene_file_transforms() {
+    use crate::{Ray, SamplerKind};
+
+    let mut sampler = SamplerKind::Random.start(0, 0, 0, 1);
+    // the scale applies before the translation above it
+    let src = "material red lambert 1 0 0\n\
+               translate 0 0 -5\n\
+               scale 2 2 2\n\
+               sphere 0 0 0 1 red\n";
+    let (scene, _) = SceneDesc::parse(src).unwrap().build(Path::new("")).unwrap();
+    let hr = scene.hit(&Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0)), &mut *sampler).unwrap();
+    assert!((hr.t - 3.0).abs() < 1e-9);
+    assert!(scene.hit(&Ray::new(Point::new(1.9, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0)), &mut *sampler).is_some());
+    assert!(scene.hit(&Ray::new(Point::new(2.1, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0)), &mut *sampler).is_none());
+
+    assert!(SceneDesc::parse("scale 1 0 1\n").is_err());
+
+    // csg operands keep their own transforms
+    let src = "material red lambert 1 0 0\n\
+               box -1 -1 -6 1 1 -4 red\n\
+               translate 0 0 -4\n\
+               sphere 0 0 0 0.5 red\n\
+               difference\n";
+    let (scene, _) = SceneDesc::parse(src).unwrap().build(Path::new("")).unwrap();
+    let hr = scene.hit(&Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0)), &mut *sampler).unwrap();
+    assert!((hr.t - 4.5).abs() < 1e-9);
+    let hr = scene.hit(&Ray::new(Point::new(0.8, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0)), &mut *sampler).unwrap();
+    assert!((hr.t - 4.0).abs() < 1e-9);
+}
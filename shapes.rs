@@ -0,0 +1,565 @@
+use crate::{Ray, Point, Vec3, Hittable, HitRecord, Material, Aabb, Sampler, sample_disk};
+use crate::triangle::area_pdf;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+const EPSILON: f64 = 1e-9;
+// flat shapes get a thin box so the slab test never sees a zero-width axis
+const BOX_PADDING: f64 = 1e-4;
+
+// Orthonormal frame with y along a shape's axis. The shapes below intersect in
+// this frame, where their math is the textbook axis-aligned one; it's rigid, so
+// ray parameters are the same in both.
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    origin: Point,
+    x: Vec3,
+    y: Vec3,
+    z: Vec3,
+}
+
+impl Frame {
+    // the shapes check the axis isn't zero, there's no direction to build on then
+    fn new(origin: Point, axis: Vec3) -> Frame {
+        let y = axis.unit();
+        let (x, z) = y.basis();
+        // keep the frame right handed whatever basis() returns
+        let z = if Vec3::dot(&Vec3::cross(&x, &y), &z) < 0.0 { -1.0 * z } else { z };
+        Frame {origin, x, y, z}
+    }
+
+    fn local(&self, v: Vec3) -> Vec3 {
+        Vec3::new(Vec3::dot(&v, &self.x), Vec3::dot(&v, &self.y), Vec3::dot(&v, &self.z))
+    }
+
+    fn world(&self, v: Vec3) -> Vec3 {
+        v.get_x() * self.x + v.get_y() * self.y + v.get_z() * self.z
+    }
+
+    fn local_ray(&self, ray: &Ray) -> Ray {
+        Ray::with_time(self.local(ray.origin - self.origin), self.local(ray.dir), ray.time)
+    }
+
+    // world box around the local box [-r, r] x [y0, y1] x [-r, r]
+    fn bounding_box(&self, r: f64, (y0, y1): (f64, f64)) -> Aabb {
+        let corner = |i: usize| {
+            let x = if i & 1 == 0 { -r } else { r };
+            let y = if i & 2 == 0 { y0 } else { y1 };
+            let z = if i & 4 == 0 { -r } else { r };
+            self.origin + self.world(Vec3::new(x, y, z))
+        };
+        let pad = Vec3::new(BOX_PADDING, BOX_PADDING, BOX_PADDING);
+        let (min, max) = (1..8).map(corner).fold((corner(0), corner(0)), |(lo, hi), p| (Vec3::min(&lo, &p), Vec3::max(&hi, &p)));
+        Aabb::new(min - pad, max + pad)
+    }
+}
+
+// angle around the local y axis as a fraction of a turn, starting at -x like the sphere's u
+fn azimuth(p: Vec3) -> f64 {
+    ((-p.get_z()).atan2(p.get_x()) + PI) / (2.0 * PI)
+}
+
+// smaller root first
+fn solve_quadratic(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
+    let discr = b * b - 4.0 * a * c;
+    if discr < 0.0 || a.abs() < EPSILON {
+        return None;
+    }
+    // avoids cancelling b against the square root
+    let q = -0.5 * (b + b.signum() * discr.sqrt());
+    let (r0, r1) = (q / a, if q != 0.0 { c / q } else { -b / (2.0 * a) });
+    Some(if r0 < r1 { (r0, r1) } else { (r1, r0) })
+}
+
+// real roots of x^3 + a x^2 + b x + c
+fn solve_cubic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    let q = (a * a - 3.0 * b) / 9.0;
+    let r = (2.0 * a * a * a - 9.0 * a * b + 27.0 * c) / 54.0;
+    if r * r < q * q * q {
+        let theta = (r / q.powf(1.5)).clamp(-1.0, 1.0).acos();
+        let s = -2.0 * q.sqrt();
+        (0..3).map(|k| s * ((theta + 2.0 * PI * k as f64) / 3.0).cos() - a / 3.0).collect()
+    } else {
+        let big = -r.signum() * (r.abs() + (r * r - q * q * q).sqrt()).cbrt();
+        let small = if big != 0.0 { q / big } else { 0.0 };
+        vec![big + small - a / 3.0]
+    }
+}
+
+// Real roots of x^4 + a x^3 + b x^2 + c x + d, unsorted, by Ferrari's method.
+// Every root gets polished with Newton steps, the closed form alone loses too
+// many digits for grazing rays.
+fn solve_quartic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    // depressed quartic y^4 + p y^2 + q y + r with x = y - a / 4
+    let a2 = a * a;
+    let p = b - 3.0 * a2 / 8.0;
+    let q = c - a * b / 2.0 + a2 * a / 8.0;
+    let r = d - a * c / 4.0 + a2 * b / 16.0 - 3.0 * a2 * a2 / 256.0;
+
+    let mut roots = Vec::with_capacity(4);
+    if q.abs() < 1e-12 {
+        // biquadratic
+        if let Some((z0, z1)) = solve_quadratic(1.0, p, r) {
+            for z in [z0, z1] {
+                if z >= 0.0 {
+                    roots.push(z.sqrt());
+                    roots.push(-z.sqrt());
+                }
+            }
+        }
+    } else {
+        // any root m > 0 of the resolvent splits the quartic into two quadratics
+        let m = solve_cubic(p, p * p / 4.0 - r, -q * q / 8.0).into_iter().fold(f64::NEG_INFINITY, f64::max);
+        if m > 0.0 {
+            let s = (2.0 * m).sqrt();
+            for (sign, shift) in [(1.0, p / 2.0 + m - q / (2.0 * s)), (-1.0, p / 2.0 + m + q / (2.0 * s))] {
+                if let Some((y0, y1)) = solve_quadratic(1.0, sign * s, shift) {
+                    roots.push(y0);
+                    roots.push(y1);
+                }
+            }
+        }
+    }
+
+    let f = |x: f64| (((x + a) * x + b) * x + c) * x + d;
+    let df = |x: f64| ((4.0 * x + 3.0 * a) * x + 2.0 * b) * x + c;
+    roots
+        .into_iter()
+        .map(|y| {
+            let mut x = y - a / 4.0;
+            for _ in 0..3 {
+                let slope = df(x);
+                if slope.abs() < EPSILON {
+                    break;
+                }
+                x -= f(x) / slope;
+            }
+            x
+        })
+        .collect()
+}
+
+// Flat disk, uv are the angle around the center and the distance from it.
+pub struct Disk {
+    frame: Frame,
+    radius: f64,
+    material: Arc<dyn Material>,
+}
+
+impl Disk {
+    /// # Panics
+    ///
+    /// If `normal` is zero.
+    pub fn new(center: Point, normal: Vec3, radius: f64, material: Arc<dyn Material>) -> Disk {
+        assert!(normal.len_sq() > 0.0, "disk normal can't be zero");
+        Disk {frame: Frame::new(center, normal), radius, material}
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, ray: &Ray, min_t: f64, max_t: f64) -> Option<HitRecord<'_>> {
+        let local = self.frame.local_ray(ray);
+        let (t, p) = hit_cap(&local, 0.0, self.radius, min_t, max_t)?;
+        let uv = (azimuth(p), p.len() / self.radius);
+        Some(HitRecord::new(ray, t, self.frame.y, uv, &*self.material, self))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.frame.bounding_box(self.radius, (0.0, 0.0)))
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_light()
+    }
+
+    fn sample_direction(&self, origin: Point, _time: f64, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        let (x, z) = sample_disk(sampler.next_2d());
+        let p = self.frame.origin + self.frame.world(Vec3::new(x * self.radius, 0.0, z * self.radius));
+        Some(p - origin)
+    }
+
+    fn direction_pdf(&self, origin: Point, dir: Vec3, _time: f64) -> f64 {
+        area_pdf(self.hit(&Ray::new(origin, dir.unit()), 0.0, f64::INFINITY), PI * self.radius * self.radius)
+    }
+}
+
+// hit of a local ray with the disk of the given radius in the plane at height y
+fn hit_cap(ray: &Ray, y: f64, radius: f64, min_t: f64, max_t: f64) -> Option<(f64, Vec3)> {
+    if ray.dir.get_y().abs() < EPSILON {
+        return None;
+    }
+    let t = (y - ray.origin.get_y()) / ray.dir.get_y();
+    if t < min_t || t > max_t {
+        return None;
+    }
+    let p = ray.at(t);
+    let p = Vec3::new(p.get_x(), 0.0, p.get_z());
+    if p.len_sq() > radius * radius {
+        return None;
+    }
+    Some((t, p))
+}
+
+// Cylinder from base to top, closed by disks unless uncapped. On the side u goes
+// around the axis and v from base to top, on the caps uv are like a Disk's.
+pub struct Cylinder {
+    frame: Frame,
+    radius: f64,
+    height: f64,
+    capped: bool,
+    material: Arc<dyn Material>,
+}
+
+impl Cylinder {
+    /// # Panics
+    ///
+    /// If `base` and `top` are the same point.
+    pub fn new(base: Point, top: Point, radius: f64, material: Arc<dyn Material>) -> Cylinder {
+        assert!((top - base).len_sq() > 0.0, "cylinder base and top can't be the same point");
+        Cylinder {frame: Frame::new(base, top - base), radius, height: (top - base).len(), capped: true, material}
+    }
+
+    // an open tube
+    pub fn uncapped(mut self) -> Self {
+        self.capped = false;
+        self
+    }
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, ray: &Ray, min_t: f64, mut max_t: f64) -> Option<HitRecord<'_>> {
+        let local = self.frame.local_ray(ray);
+        let (o, d) = (local.origin, local.dir);
+        // (local point, local outward normal, uv) of the nearest hit so far
+        let mut best: Option<(f64, Vec3, (f64, f64))> = None;
+
+        let a = d.get_x() * d.get_x() + d.get_z() * d.get_z();
+        let b = 2.0 * (o.get_x() * d.get_x() + o.get_z() * d.get_z());
+        let c = o.get_x() * o.get_x() + o.get_z() * o.get_z() - self.radius * self.radius;
+        if let Some((t0, t1)) = solve_quadratic(a, b, c) {
+            for t in [t0, t1] {
+                let y = o.get_y() + t * d.get_y();
+                if t >= min_t && t <= max_t && (0.0..=self.height).contains(&y) {
+                    let p = local.at(t);
+                    best = Some((t, Vec3::new(p.get_x(), 0.0, p.get_z()) / self.radius, (azimuth(p), y / self.height)));
+                    max_t = t;
+                    break;
+                }
+            }
+        }
+        if self.capped {
+            for (y, normal) in [(0.0, -1.0), (self.height, 1.0)] {
+                if let Some((t, p)) = hit_cap(&local, y, self.radius, min_t, max_t) {
+                    best = Some((t, Vec3::new(0.0, normal, 0.0), (azimuth(p), p.len() / self.radius)));
+                    max_t = t;
+                }
+            }
+        }
+
+        let (t, normal, uv) = best?;
+        Some(HitRecord::new(ray, t, self.frame.world(normal), uv, &*self.material, self))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.frame.bounding_box(self.radius, (0.0, self.height)))
+    }
+}
+
+// Cone with its base disk around base and its tip at apex, the base is closed
+// unless uncapped. uv are like a Cylinder's.
+pub struct Cone {
+    frame: Frame,
+    radius: f64,
+    height: f64,
+    capped: bool,
+    material: Arc<dyn Material>,
+}
+
+impl Cone {
+    /// # Panics
+    ///
+    /// If `base` and `apex` are the same point.
+    pub fn new(base: Point, apex: Point, radius: f64, material: Arc<dyn Material>) -> Cone {
+        assert!((apex - base).len_sq() > 0.0, "cone base and apex can't be the same point");
+        Cone {frame: Frame::new(base, apex - base), radius, height: (apex - base).len(), capped: true, material}
+    }
+
+    pub fn uncapped(mut self) -> Self {
+        self.capped = false;
+        self
+    }
+}
+
+impl Hittable for Cone {
+    fn hit(&self, ray: &Ray, min_t: f64, mut max_t: f64) -> Option<HitRecord<'_>> {
+        let local = self.frame.local_ray(ray);
+        let (o, d) = (local.origin, local.dir);
+        let mut best: Option<(f64, Vec3, (f64, f64))> = None;
+
+        // x^2 + z^2 = k^2 (h - y)^2, k being the radius shrinking per unit of height
+        let k2 = (self.radius / self.height).powi(2);
+        let (oy, dy) = (self.height - o.get_y(), -d.get_y());
+        let a = d.get_x() * d.get_x() + d.get_z() * d.get_z() - k2 * dy * dy;
+        let b = 2.0 * (o.get_x() * d.get_x() + o.get_z() * d.get_z() - k2 * oy * dy);
+        let c = o.get_x() * o.get_x() + o.get_z() * o.get_z() - k2 * oy * oy;
+        if let Some((t0, t1)) = solve_quadratic(a, b, c) {
+            for t in [t0, t1] {
+                let p = local.at(t);
+                // the equation also holds on the mirrored cone above the apex
+                if t >= min_t && t <= max_t && (0.0..=self.height).contains(&p.get_y()) {
+                    let normal = Vec3::new(p.get_x(), k2 * (self.height - p.get_y()), p.get_z()).unit();
+                    best = Some((t, normal, (azimuth(p), p.get_y() / self.height)));
+                    max_t = t;
+                    break;
+                }
+            }
+        }
+        if self.capped {
+            if let Some((t, p)) = hit_cap(&local, 0.0, self.radius, min_t, max_t) {
+                best = Some((t, Vec3::new(0.0, -1.0, 0.0), (azimuth(p), p.len() / self.radius)));
+            }
+        }
+
+        let (t, normal, uv) = best?;
+        Some(HitRecord::new(ray, t, self.frame.world(normal), uv, &*self.material, self))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.frame.bounding_box(self.radius, (0.0, self.height)))
+    }
+}
+
+// Torus around the axis through center: a tube of radius minor swept along a circle
+// of radius major. u goes around the axis, v around the tube starting outside.
+pub struct Torus {
+    frame: Frame,
+    major: f64,
+    minor: f64,
+    material: Arc<dyn Material>,
+}
+
+impl Torus {
+    /// # Panics
+    ///
+    /// If `axis` is zero.
+    pub fn new(center: Point, axis: Vec3, major: f64, minor: f64, material: Arc<dyn Material>) -> Torus {
+        assert!(axis.len_sq() > 0.0, "torus axis can't be zero");
+        Torus {frame: Frame::new(center, axis), major, minor, material}
+    }
+}
+
+impl Hittable for Torus {
+    fn hit(&self, ray: &Ray, min_t: f64, max_t: f64) -> Option<HitRecord<'_>> {
+        let local = self.frame.local_ray(ray);
+        // a unit direction starting next to the torus keeps the quartic well conditioned
+        let len = local.dir.len();
+        let d = local.dir / len;
+        let bound = self.major + self.minor;
+        let (enter, exit) = solve_quadratic(1.0, 2.0 * Vec3::dot(&local.origin, &d), local.origin.len_sq() - bound * bound)?;
+        if exit < 0.0 {
+            return None;
+        }
+        let start = enter.max(0.0);
+        let o = local.origin + start * d;
+
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + z^2)
+        let (r2, big_r2) = (self.minor * self.minor, self.major * self.major);
+        let n = Vec3::dot(&o, &d);
+        let k = o.len_sq() + big_r2 - r2;
+        let dxz = d.get_x() * d.get_x() + d.get_z() * d.get_z();
+        let oxz = o.get_x() * d.get_x() + o.get_z() * d.get_z();
+        let o2xz = o.get_x() * o.get_x() + o.get_z() * o.get_z();
+        let roots = solve_quartic(
+            4.0 * n,
+            4.0 * n * n + 2.0 * k - 4.0 * big_r2 * dxz,
+            4.0 * n * k - 8.0 * big_r2 * oxz,
+            k * k - 4.0 * big_r2 * o2xz,
+        );
+        let t = roots
+            .into_iter()
+            .map(|s| (s + start) / len)
+            .filter(|t| *t >= min_t && *t <= max_t)
+            .fold(f64::INFINITY, f64::min);
+        if !t.is_finite() {
+            return None;
+        }
+
+        let p = local.at(t);
+        let ring = (p.get_x() * p.get_x() + p.get_z() * p.get_z()).sqrt();
+        // the tube's center closest to p
+        let center = if ring > 0.0 { self.major / ring * Vec3::new(p.get_x(), 0.0, p.get_z()) } else { Vec3::new(self.major, 0.0, 0.0) };
+        let normal = (p - center).unit();
+        let v = (p.get_y().atan2(ring - self.major) / (2.0 * PI)).rem_euclid(1.0);
+        Some(HitRecord::new(ray, t, self.frame.world(normal), (azimuth(p), v), &*self.material, self))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.frame.bounding_box(self.major + self.minor, (-self.minor, self.minor)))
+    }
+}
+
+// Cylinder between a and b with hemispheres on both ends. v runs from the tip past
+// a to the tip past b.
+pub struct Capsule {
+    frame: Frame,
+    radius: f64,
+    length: f64,
+    material: Arc<dyn Material>,
+}
+
+impl Capsule {
+    /// # Panics
+    ///
+    /// If `a` and `b` are the same point.
+    pub fn new(a: Point, b: Point, radius: f64, material: Arc<dyn Material>) -> Capsule {
+        assert!((b - a).len_sq() > 0.0, "capsule end points can't be the same point, that's a sphere");
+        Capsule {frame: Frame::new(a, b - a), radius, length: (b - a).len(), material}
+    }
+}
+
+impl Hittable for Capsule {
+    fn hit(&self, ray: &Ray, min_t: f64, max_t: f64) -> Option<HitRecord<'_>> {
+        let local = self.frame.local_ray(ray);
+        let (o, d) = (local.origin, local.dir);
+        let r2 = self.radius * self.radius;
+        let mut best: Option<(f64, Vec3)> = None;
+        let mut closest = max_t;
+
+        let a = d.get_x() * d.get_x() + d.get_z() * d.get_z();
+        let b = 2.0 * (o.get_x() * d.get_x() + o.get_z() * d.get_z());
+        let c = o.get_x() * o.get_x() + o.get_z() * o.get_z() - r2;
+        if let Some((t0, t1)) = solve_quadratic(a, b, c) {
+            for t in [t0, t1] {
+                let p = local.at(t);
+                if t >= min_t && t <= closest && (0.0..=self.length).contains(&p.get_y()) {
+                    best = Some((t, Vec3::new(p.get_x(), 0.0, p.get_z()) / self.radius));
+                    closest = t;
+                    break;
+                }
+            }
+        }
+        // only the outer half of each end sphere belongs to the capsule
+        for (end, outside) in [(0.0, -1.0), (self.length, 1.0)] {
+            let center = Vec3::new(0.0, end, 0.0);
+            let oc = o - center;
+            if let Some((t0, t1)) = solve_quadratic(d.len_sq(), 2.0 * Vec3::dot(&oc, &d), oc.len_sq() - r2) {
+                for t in [t0, t1] {
+                    let normal = (local.at(t) - center) / self.radius;
+                    if t >= min_t && t <= closest && normal.get_y() * outside >= 0.0 {
+                        best = Some((t, normal));
+                        closest = t;
+                        break;
+                    }
+                }
+            }
+        }
+
+        let (t, normal) = best?;
+        let p = local.at(t);
+        let v = (p.get_y() + self.radius) / (self.length + 2.0 * self.radius);
+        Some(HitRecord::new(ray, t, self.frame.world(normal), (azimuth(p), v), &*self.material, self))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        let (a, b) = (self.frame.origin, self.frame.origin + self.length * self.frame.y);
+        Some(Aabb::new(Vec3::min(&a, &b) - r, Vec3::max(&a, &b) + r))
+    }
+}
+
+#[cfg(test)]
+fn close(a: Vec3, b: Vec3) -> bool {
+    (a - b).len() < 1e-9
+}
+
+#[test]
+fn test_solve_quartic() {
+    // (x - 1)(x + 2)(x - 3)(x - 0.5)
+    let mut roots = solve_quartic(-2.5, -4.0, 8.5, -3.0);
+    roots.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let expected = [-2.0, 0.5, 1.0, 3.0];
+    assert_eq!(roots.len(), 4);
+    assert!(roots.iter().zip(expected.iter()).all(|(r, e)| (r - e).abs() < 1e-9), "{:?}", roots);
+    // x^4 + 1 has no real roots
+    assert!(solve_quartic(0.0, 0.0, 0.0, 1.0).is_empty());
+}
+
+#[test]
+fn test_disk_cylinder_cone() {
+    let material: Arc<dyn Material> = Arc::new(crate::Normal);
+    let down = |x: f64| Ray::new(Point::new(x, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+    let across = |y: f64| Ray::new(Point::new(-5.0, y, 0.0), Vec3::new(1.0, 0.0, 0.0));
+
+    let disk = Disk::new(Point::new(0.0, 1.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 2.0, material.clone());
+    let hr = disk.hit(&down(1.0), 0.0, f64::INFINITY).unwrap();
+    assert_eq!(hr.t, 4.0);
+    assert!(close(hr.normal, Vec3::new(0.0, 1.0, 0.0)) && hr.front_face);
+    assert!((hr.v - 0.5).abs() < 1e-12);
+    assert!(disk.hit(&down(2.5), 0.0, f64::INFINITY).is_none());
+
+    let cylinder = Cylinder::new(Point::new(0.0, 0.0, 0.0), Point::new(0.0, 2.0, 0.0), 1.0, material.clone());
+    let hr = cylinder.hit(&across(1.0), 0.0, f64::INFINITY).unwrap();
+    assert!((hr.t - 4.0).abs() < 1e-12 && (hr.v - 0.5).abs() < 1e-12);
+    assert!(close(hr.normal, Vec3::new(-1.0, 0.0, 0.0)));
+    let top = cylinder.hit(&down(0.5), 0.0, f64::INFINITY).unwrap();
+    assert_eq!(top.t, 3.0);
+    assert!(close(top.normal, Vec3::new(0.0, 1.0, 0.0)));
+    assert!(cylinder.hit(&across(2.5), 0.0, f64::INFINITY).is_none());
+    // through the open end the inside of the wall is hit from behind
+    let tube = Cylinder::new(Point::new(0.0, 0.0, 0.0), Point::new(0.0, 2.0, 0.0), 1.0, material.clone()).uncapped();
+    let inside = Ray::new(Point::new(0.0, 2.5, 0.0), Vec3::new(0.5, -1.0, 0.0));
+    let hr = tube.hit(&inside, 0.0, f64::INFINITY).unwrap();
+    assert!((hr.p.get_y() - 0.5).abs() < 1e-9 && !hr.front_face);
+    assert!(tube.hit(&down(0.5), 0.0, f64::INFINITY).is_none());
+
+    let cone = Cone::new(Point::new(0.0, 0.0, 0.0), Point::new(0.0, 2.0, 0.0), 1.0, material.clone());
+    let hr = cone.hit(&across(1.0), 0.0, f64::INFINITY).unwrap();
+    assert!((hr.t - 4.5).abs() < 1e-12);
+    assert!(close(hr.normal, Vec3::new(-2.0, 1.0, 0.0).unit()));
+    assert!(cone.hit(&across(2.1), 0.0, f64::INFINITY).is_none());
+    let base = cone.hit(&Ray::new(Point::new(0.2, -3.0, 0.0), Vec3::new(0.0, 1.0, 0.0)), 0.0, f64::INFINITY).unwrap();
+    assert_eq!(base.t, 3.0);
+    assert!(close(base.normal, Vec3::new(0.0, -1.0, 0.0)));
+    // tilted onto the x axis
+    let sideways = Cone::new(Point::new(0.0, 0.0, 0.0), Point::new(2.0, 0.0, 0.0), 1.0, material);
+    let hr = sideways.hit(&down(1.0), 0.0, f64::INFINITY).unwrap();
+    assert!((hr.t - 4.5).abs() < 1e-12);
+}
+
+#[test]
+fn test_torus_capsule() {
+    let material: Arc<dyn Material> = Arc::new(crate::Normal);
+    let torus = Torus::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 2.0, 0.5, material.clone());
+    let hr = torus.hit(&Ray::new(Point::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0)), 0.0, f64::INFINITY).unwrap();
+    assert!((hr.t - 2.5).abs() < 1e-9);
+    assert!(close(hr.normal, Vec3::new(-1.0, 0.0, 0.0)));
+    // starting in the hole, the inner side of the tube is next
+    let hr = torus.hit(&Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 2.0)), 0.0, f64::INFINITY).unwrap();
+    assert!((hr.t - 0.75).abs() < 1e-9);
+    let hr = torus.hit(&Ray::new(Point::new(2.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0)), 0.0, f64::INFINITY).unwrap();
+    assert!((hr.t - 4.5).abs() < 1e-9);
+    assert!(close(hr.normal, Vec3::new(0.0, 1.0, 0.0)));
+    assert!((hr.v - 0.25).abs() < 1e-9);
+    assert!(torus.hit(&Ray::new(Point::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0)), 0.0, f64::INFINITY).is_none());
+    // grazing the top of the tube from far away
+    let far = Ray::new(Point::new(-1000.0, 0.4999, 0.0), Vec3::new(1.0, 0.0, 0.0));
+    assert!(torus.hit(&far, 0.0, f64::INFINITY).is_some());
+
+    let capsule = Capsule::new(Point::new(0.0, 0.0, 0.0), Point::new(0.0, 2.0, 0.0), 0.5, material);
+    let hr = capsule.hit(&Ray::new(Point::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0)), 0.0, f64::INFINITY).unwrap();
+    assert!((hr.t - 2.5).abs() < 1e-12 && (hr.v - 1.0).abs() < 1e-12);
+    assert!(close(hr.normal, Vec3::new(0.0, 1.0, 0.0)));
+    let hr = capsule.hit(&Ray::new(Point::new(-5.0, 1.0, 0.0), Vec3::new(1.0, 0.0, 0.0)), 0.0, f64::INFINITY).unwrap();
+    assert!((hr.t - 4.5).abs() < 1e-12);
+    assert!(close(hr.normal, Vec3::new(-1.0, 0.0, 0.0)));
+    let hr = capsule.hit(&Ray::new(Point::new(-5.0, -0.3, 0.0), Vec3::new(1.0, 0.0, 0.0)), 0.0, f64::INFINITY).unwrap();
+    assert!((hr.t - 4.6).abs() < 1e-12);
+    assert!(capsule.hit(&Ray::new(Point::new(-5.0, -0.6, 0.0), Vec3::new(1.0, 0.0, 0.0)), 0.0, f64::INFINITY).is_none());
+}
+
+#[test]
+#[should_panic(expected = "disk normal can't be zero")]
+fn test_zero_disk_normal() {
+    use crate::{LambertDiffuse, Color};
+    Disk::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 0.0), 1.0, Arc::new(LambertDiffuse::new(Color::new(1.0, 1.0, 1.0))));
+}
@@ -0,0 +1,203 @@
+use crate::{Point, Vec3, Color, Image};
+use rand::SeedableRng;
+use rand::seq::SliceRandom;
+use rand_pcg::Pcg32;
+use std::fmt::Debug;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+// Color varying over a surface, looked up by the hit's (u, v) and position.
+pub trait Texture: Send + Sync + Debug {
+    fn value(&self, u: f64, v: f64, p: Point) -> Color;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SolidColor(pub Color);
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: Point) -> Color {
+        self.0
+    }
+}
+
+// 3D checkerboard of cubes with side 1 / scale, so it doesn't depend on the uv mapping.
+#[derive(Debug, Clone)]
+pub struct Checker {
+    pub even: Arc<dyn Texture>,
+    pub odd: Arc<dyn Texture>,
+    pub scale: f64,
+}
+
+impl Checker {
+    pub fn new(even: Arc<dyn Texture>, odd: Arc<dyn Texture>, scale: f64) -> Checker {
+        Checker {even, odd, scale}
+    }
+}
+
+impl Texture for Checker {
+    fn value(&self, u: f64, v: f64, p: Point) -> Color {
+        let cell = |x: f64| (x * self.scale).floor() as i64;
+        if (cell(p.get_x()) + cell(p.get_y()) + cell(p.get_z())).rem_euclid(2) == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+const PERLIN_POINTS: usize = 256;
+
+// Gradient noise on the integer lattice, values roughly in [-1, 1].
+#[derive(Debug, Clone)]
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    perm: [Vec<usize>; 3],
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Perlin {
+        let mut rng = Pcg32::seed_from_u64(seed);
+        let gradients = (0..PERLIN_POINTS).map(|_| Vec3::random_unit(&mut rng)).collect();
+        let mut perm = || {
+            let mut p: Vec<usize> = (0..PERLIN_POINTS).collect();
+            p.shuffle(&mut rng);
+            p
+        };
+        Perlin {gradients, perm: [perm(), perm(), perm()]}
+    }
+
+    pub fn noise(&self, p: Point) -> f64 {
+        let (x, y, z) = (p.get_x(), p.get_y(), p.get_z());
+        let (i, j, k) = (x.floor() as i64, y.floor() as i64, z.floor() as i64);
+        let (u, v, w) = (x - x.floor(), y - y.floor(), z - z.floor());
+        // Hermite smoothing hides the lattice
+        let smooth = |t: f64| t * t * (3.0 - 2.0 * t);
+        let (su, sv, sw) = (smooth(u), smooth(v), smooth(w));
+
+        let wrap = |n: i64| (n & (PERLIN_POINTS as i64 - 1)) as usize;
+        let mut sum = 0.0;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let idx = self.perm[0][wrap(i + di)] ^ self.perm[1][wrap(j + dj)] ^ self.perm[2][wrap(k + dk)];
+                    let offset = Vec3::new(u - di as f64, v - dj as f64, w - dk as f64);
+                    let weight = |d: i64, s: f64| if d == 1 { s } else { 1.0 - s };
+                    sum += weight(di, su) * weight(dj, sv) * weight(dk, sw) * Vec3::dot(&self.gradients[idx], &offset);
+                }
+            }
+        }
+        sum
+    }
+
+    // sum of octaves of decreasing size and weight, always positive
+    pub fn turbulence(&self, p: Point, depth: u32) -> f64 {
+        let (mut sum, mut p, mut weight) = (0.0, p, 1.0);
+        for _ in 0..depth {
+            sum += weight * self.noise(p);
+            weight *= 0.5;
+            p = 2.0 * p;
+        }
+        sum.abs()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoisePattern {
+    Noise,
+    Turbulence,
+    // stripes along z, bent by turbulence
+    Marble,
+}
+
+const TURBULENCE_DEPTH: u32 = 7;
+
+// Grey procedural texture, noise features are about 1 / scale in size, for marble
+// the stripe spacing.
+#[derive(Debug, Clone)]
+pub struct NoiseTexture {
+    perlin: Perlin,
+    pattern: NoisePattern,
+    scale: f64,
+}
+
+impl NoiseTexture {
+    pub fn new(pattern: NoisePattern, scale: f64, seed: u64) -> NoiseTexture {
+        NoiseTexture {perlin: Perlin::new(seed), pattern, scale}
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, p: Point) -> Color {
+        let grey = match self.pattern {
+            NoisePattern::Noise => 0.5 * (1.0 + self.perlin.noise(self.scale * p)),
+            NoisePattern::Turbulence => self.perlin.turbulence(self.scale * p, TURBULENCE_DEPTH),
+            // only the stripes are scaled, the veins keep their size
+            NoisePattern::Marble => {
+                let phase = self.scale * p.get_z() + 10.0 * self.perlin.turbulence(p, TURBULENCE_DEPTH);
+                0.5 * (1.0 + phase.sin())
+            },
+        };
+        grey * Color::new(1.0, 1.0, 1.0)
+    }
+}
+
+// Image wrapped over the uv square, v = 0 is the bottom row. Nearest pixel lookup.
+#[derive(Debug, Clone)]
+pub struct ImageTexture {
+    image: Image,
+}
+
+impl ImageTexture {
+    pub fn new(image: Image) -> ImageTexture {
+        ImageTexture {image}
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<ImageTexture> {
+        Ok(ImageTexture::new(Image::load(path)?))
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: Point) -> Color {
+        let (w, h) = (self.image.width(), self.image.height());
+        if w == 0 || h == 0 {
+            return Color::new(1.0, 0.0, 1.0);
+        }
+        let u = u.rem_euclid(1.0);
+        let v = 1.0 - v.rem_euclid(1.0);
+        let x = ((u * w as f64) as usize).min(w - 1);
+        let y = ((v * h as f64) as usize).min(h - 1);
+        self.image.get(x, y)
+    }
+}
+
+#[test]
+fn test_textures() {
+    let white: Arc<dyn Texture> = Arc::new(SolidColor(Color::new(1.0, 1.0, 1.0)));
+    let black: Arc<dyn Texture> = Arc::new(SolidColor(Color::new(0.0, 0.0, 0.0)));
+    let checker = Checker::new(white, black, 2.0);
+    assert_eq!(checker.value(0.0, 0.0, Point::new(0.1, 0.1, 0.1)), Color::new(1.0, 1.0, 1.0));
+    assert_eq!(checker.value(0.0, 0.0, Point::new(0.6, 0.1, 0.1)), Color::new(0.0, 0.0, 0.0));
+    assert_eq!(checker.value(0.0, 0.0, Point::new(-0.1, 0.1, 0.1)), Color::new(0.0, 0.0, 0.0));
+
+    let perlin = Perlin::new(3);
+    let mut rng = Pcg32::seed_from_u64(0);
+    for _ in 0..100 {
+        let p = Vec3::random(&mut rng, -10.0, 10.0);
+        assert!(perlin.noise(p).abs() <= 1.5);
+        // continuous, and zero on the lattice
+        assert!((perlin.noise(p) - perlin.noise(p + Vec3::new(1e-7, 0.0, 0.0))).abs() < 1e-5);
+        let lattice = Point::new(p.get_x().round(), p.get_y().round(), p.get_z().round());
+        assert!(perlin.noise(lattice).abs() < 1e-12);
+    }
+    assert_eq!(Perlin::new(3).noise(Point::new(0.5, 1.3, 2.7)), perlin.noise(Point::new(0.5, 1.3, 2.7)));
+
+    let mut image = Image::new(2, 2);
+    image.set(0, 1, Color::new(1.0, 0.0, 0.0));
+    image.set(1, 0, Color::new(0.0, 1.0, 0.0));
+    let texture = ImageTexture::new(image);
+    assert_eq!(texture.value(0.25, 0.25, Point::new(0.0, 0.0, 0.0)), Color::new(1.0, 0.0, 0.0));
+    assert_eq!(texture.value(0.75, 0.75, Point::new(0.0, 0.0, 0.0)), Color::new(0.0, 1.0, 0.0));
+    assert_eq!(texture.value(1.25, -0.75, Point::new(0.0, 0.0, 0.0)), Color::new(1.0, 0.0, 0.0));
+}
@@ -0,0 +1,178 @@
+use crate::{Color, Image};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToneMap {
+    Clamp,
+    Reinhard,
+    // radiance that maps to pure white
+    ExtendedReinhard(f64),
+    Aces,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transfer {
+    Linear,
+    Gamma(f64),
+    Srgb,
+}
+
+// Turns linear radiance into display values: exposure, tone map, transfer function
+// and finally (optionally dithered) 8-bit quantization.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PostProcess {
+    pub exposure: f64,
+    pub tone_map: ToneMap,
+    pub transfer: Transfer,
+    pub dither: bool,
+}
+
+impl Default for PostProcess {
+    fn default() -> Self {
+        PostProcess {exposure: 0.0, tone_map: ToneMap::Clamp, transfer: Transfer::Srgb, dither: true}
+    }
+}
+
+impl ToneMap {
+    pub fn from_name(name: &str) -> Option<ToneMap> {
+        match name.to_ascii_lowercase().as_str() {
+            "clamp" => Some(ToneMap::Clamp),
+            "reinhard" => Some(ToneMap::Reinhard),
+            "aces" => Some(ToneMap::Aces),
+            name => name
+                .strip_prefix("reinhard:")
+                .and_then(|white| white.parse().ok())
+                // anything else divides by zero or flips the curve
+                .filter(|white: &f64| white.is_finite() && *white > 0.0)
+                .map(ToneMap::ExtendedReinhard),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            ToneMap::Clamp => "clamp".to_string(),
+            ToneMap::Reinhard => "reinhard".to_string(),
+            ToneMap::ExtendedReinhard(white) => format!("reinhard:{}", white),
+            ToneMap::Aces => "aces".to_string(),
+        }
+    }
+
+    pub fn map(&self, x: f64) -> f64 {
+        let x = x.max(0.0);
+        let y = match *self {
+            ToneMap::Clamp => x,
+            ToneMap::Reinhard => x / (1.0 + x),
+            ToneMap::ExtendedReinhard(white) => x * (1.0 + x / (white * white)) / (1.0 + x),
+            // Narkowicz's fit of the ACES filmic curve
+            ToneMap::Aces => (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14),
+        };
+        y.min(1.0)
+    }
+}
+
+impl Transfer {
+    pub fn from_name(name: &str) -> Option<Transfer> {
+        match name.to_ascii_lowercase().as_str() {
+            "linear" => Some(Transfer::Linear),
+            "srgb" => Some(Transfer::Srgb),
+            name => name
+                .strip_prefix("gamma:")
+                .and_then(|gamma| gamma.parse().ok())
+                .filter(|gamma: &f64| valid_gamma(*gamma))
+                .map(Transfer::Gamma),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Transfer::Linear => "linear".to_string(),
+            Transfer::Gamma(gamma) => format!("gamma:{}", gamma),
+            Transfer::Srgb => "srgb".to_string(),
+        }
+    }
+
+    pub fn encode(&self, x: f64) -> f64 {
+        match *self {
+            Transfer::Linear => x,
+            Transfer::Gamma(gamma) => x.powf(1.0 / gamma),
+            Transfer::Srgb if x <= 0.0031308 => 12.92 * x,
+            Transfer::Srgb => 1.055 * x.powf(1.0 / 2.4) - 0.055,
+        }
+    }
+
+    // inverse of encode, for reading 8-bit images back as linear values
+    pub fn decode(&self, x: f64) -> f64 {
+        match *self {
+            Transfer::Linear => x,
+            Transfer::Gamma(gamma) => x.powf(gamma),
+            Transfer::Srgb if x <= 0.04045 => x / 12.92,
+            Transfer::Srgb => ((x + 0.055) / 1.055).powf(2.4),
+        }
+    }
+}
+
+// anything else raises to an infinite or NaN power
+pub fn valid_gamma(gamma: f64) -> bool {
+    gamma.is_finite() && gamma > 0.0
+}
+
+// cheap integer hash so the dither pattern doesn't depend on any RNG state
+fn hash_noise(x: usize, y: usize, channel: usize) -> f64 {
+    let mut h = (x as u32).wrapping_mul(0x8da6_b343) ^ (y as u32).wrapping_mul(0xd816_3841) ^ (channel as u32).wrapping_mul(0xcb1a_b31f);
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7feb_352d);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846c_a68b);
+    h ^= h >> 16;
+    h as f64 / u32::MAX as f64
+}
+
+impl PostProcess {
+    // display value in [0, 1] for each channel
+    pub fn apply(&self, c: Color) -> Color {
+        let c = 2f64.powf(self.exposure) * c;
+        let f = |x: f64| self.transfer.encode(self.tone_map.map(x));
+        Color::new(f(c.get_x()), f(c.get_y()), f(c.get_z()))
+    }
+
+    pub fn to_rgb8(&self, image: &Image) -> Vec<u8> {
+        let mut out = Vec::with_capacity(image.width() * image.height() * 3);
+        for y in 0..image.height() {
+            for x in 0..image.width() {
+                let c = self.apply(image.get(x, y));
+                for (channel, v) in [c.get_x(), c.get_y(), c.get_z()].iter().enumerate() {
+                    // triangular noise of one quantization step hides banding in gradients
+                    let noise = if self.dither {
+                        hash_noise(x, y, channel) + hash_noise(x, y, channel + 3) - 1.0
+                    } else {
+                        0.0
+                    };
+                    out.push((v * 255.0 + noise + 0.5).floor().clamp(0.0, 255.0) as u8);
+                }
+            }
+        }
+        out
+    }
+}
+
+#[test]
+fn test_tone_maps() {
+    assert_eq!(ToneMap::Clamp.map(3.0), 1.0);
+    assert_eq!(ToneMap::Reinhard.map(1.0), 0.5);
+    assert_eq!(ToneMap::ExtendedReinhard(4.0).map(4.0), 1.0);
+    assert!((ToneMap::Aces.map(0.18) - 0.2672).abs() < 1e-3);
+    assert_eq!(ToneMap::from_name("reinhard:2.5"), Some(ToneMap::ExtendedReinhard(2.5)));
+    for bad in ["reinhard:0", "reinhard:-2", "reinhard:inf", "reinhard:nan"] {
+        assert_eq!(ToneMap::from_name(bad), None);
+    }
+    assert!((Transfer::Srgb.encode(0.5) - 0.7354).abs() < 1e-3);
+    assert_eq!(Transfer::from_name("gamma:2.2"), Some(Transfer::Gamma(2.2)));
+    assert_eq!(Transfer::from_name(&Transfer::Gamma(1.8).name()), Some(Transfer::Gamma(1.8)));
+    for bad in ["gamma:0", "gamma:-2", "gamma:inf", "gamma:nan", "gamma"] {
+        assert_eq!(Transfer::from_name(bad), None);
+    }
+
+    let mut img = Image::new(1, 1);
+    img.set(0, 0, Color::new(1.0, 0.25, 0.0));
+    let post = PostProcess {exposure: 0.0, tone_map: ToneMap::Clamp, transfer: Transfer::Gamma(2.0), dither: false};
+    assert_eq!(post.to_rgb8(&img), vec![255, 128, 0]);
+}
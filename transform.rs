@@ -0,0 +1,284 @@
+use crate::{Ray, Point, Vec3, Aabb, Hittable, HitRecord, Sampler};
+use std::sync::Arc;
+
+type Matrix = [[f64; 4]; 4];
+
+const IDENTITY: Matrix = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+fn mul(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut m = [[0.0; 4]; 4];
+    for (i, row) in m.iter_mut().enumerate() {
+        for (j, x) in row.iter_mut().enumerate() {
+            *x = (0..4).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    m
+}
+
+fn transpose(a: &Matrix) -> Matrix {
+    let mut m = [[0.0; 4]; 4];
+    for (i, row) in m.iter_mut().enumerate() {
+        for (j, x) in row.iter_mut().enumerate() {
+            *x = a[j][i];
+        }
+    }
+    m
+}
+
+// Affine transform, kept together with its inverse so neither ever has to be
+// computed from the other.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    m: Matrix,
+    inv: Matrix,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::identity()
+    }
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform {m: IDENTITY, inv: IDENTITY}
+    }
+
+    pub fn translate(offset: Vec3) -> Transform {
+        let mut m = IDENTITY;
+        let mut inv = IDENTITY;
+        for axis in 0..3 {
+            m[axis][3] = offset[axis];
+            inv[axis][3] = -offset[axis];
+        }
+        Transform {m, inv}
+    }
+
+    // factors must not be 0
+    pub fn scale(factors: Vec3) -> Transform {
+        let mut m = IDENTITY;
+        let mut inv = IDENTITY;
+        for axis in 0..3 {
+            m[axis][axis] = factors[axis];
+            inv[axis][axis] = 1.0 / factors[axis];
+        }
+        Transform {m, inv}
+    }
+
+    // counterclockwise looking down the axis towards the origin
+    pub fn rotate(axis: Vec3, degrees: f64) -> Transform {
+        let a = axis.unit();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let mut m = IDENTITY;
+        for i in 0..3 {
+            for j in 0..3 {
+                m[i][j] = a[i] * a[j] * (1.0 - cos) + if i == j { cos } else { 0.0 };
+            }
+        }
+        m[0][1] -= a[2] * sin;
+        m[0][2] += a[1] * sin;
+        m[1][0] += a[2] * sin;
+        m[1][2] -= a[0] * sin;
+        m[2][0] -= a[1] * sin;
+        m[2][1] += a[0] * sin;
+        Transform {m, inv: transpose(&m)}
+    }
+
+    pub fn rotate_x(degrees: f64) -> Transform {
+        Transform::rotate(Vec3::new(1.0, 0.0, 0.0), degrees)
+    }
+
+    pub fn rotate_y(degrees: f64) -> Transform {
+        Transform::rotate(Vec3::new(0.0, 1.0, 0.0), degrees)
+    }
+
+    pub fn rotate_z(degrees: f64) -> Transform {
+        Transform::rotate(Vec3::new(0.0, 0.0, 1.0), degrees)
+    }
+
+    // applies self first, then next
+    pub fn then(&self, next: &Transform) -> Transform {
+        Transform {m: mul(&next.m, &self.m), inv: mul(&self.inv, &next.inv)}
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Transform {m: self.inv, inv: self.m}
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.m == IDENTITY
+    }
+
+    pub fn point(&self, p: Point) -> Point {
+        self.vector(p) + Vec3::new(self.m[0][3], self.m[1][3], self.m[2][3])
+    }
+
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        let row = |r: &[f64; 4]| r[0] * v[0] + r[1] * v[1] + r[2] * v[2];
+        Vec3::new(row(&self.m[0]), row(&self.m[1]), row(&self.m[2]))
+    }
+
+    // normals go through the inverse transpose to stay perpendicular, the result isn't unit length
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        let col = |c: usize| self.inv[0][c] * n[0] + self.inv[1][c] * n[1] + self.inv[2][c] * n[2];
+        Vec3::new(col(0), col(1), col(2))
+    }
+
+    // how much volumes grow
+    pub fn determinant(&self) -> f64 {
+        let m = &self.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    pub fn bounding_box(&self, bbox: &Aabb) -> Aabb {
+        let corner = |i: usize| {
+            let pick = |bit: usize, axis: usize| if i & bit == 0 { bbox.min[axis] } else { bbox.max[axis] };
+            self.point(Point::new(pick(1, 0), pick(2, 1), pick(4, 2)))
+        };
+        let first = corner(0);
+        (1..8).map(corner).fold(Aabb::new(first, first), |acc, p| Aabb::new(Vec3::min(&acc.min, &p), Vec3::max(&acc.max, &p)))
+    }
+}
+
+// Places a shared object in the scene through a transform. Rays are moved into
+// the object's space instead of moving the object, so the same mesh and its BVH
+// can be instanced any number of times.
+pub struct Instance {
+    object: Arc<dyn Hittable>,
+    transform: Transform,
+}
+
+impl Instance {
+    pub fn new(object: Arc<dyn Hittable>, transform: Transform) -> Instance {
+        Instance {object, transform}
+    }
+
+    // the direction isn't normalized so ray parameters are the same in both spaces
+    fn local_ray(&self, ray: &Ray) -> Ray {
+        let inv = self.transform.inverse();
+        Ray::with_time(inv.point(ray.origin), inv.vector(ray.dir), ray.time)
+    }
+
+    // moves a hit of the local ray back to world space
+    fn world_hit<'a>(&'a self, ray: &Ray, mut hr: HitRecord<'a>) -> HitRecord<'a> {
+        hr.p = ray.at(hr.t);
+        hr.normal = self.transform.normal(hr.normal).unit();
+        hr.geometric_normal = self.transform.normal(hr.geometric_normal).unit();
+        hr.ray_dir = ray.dir;
+        hr.object = self;
+        hr
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, ray: &Ray, min_t: f64, max_t: f64) -> Option<HitRecord<'_>> {
+        let hr = self.object.hit(&self.local_ray(ray), min_t, max_t)?;
+        Some(self.world_hit(ray, hr))
+    }
+
+    fn hit_sampled(&self, ray: &Ray, min_t: f64, max_t: f64, sampler: &mut dyn Sampler) -> Option<HitRecord<'_>> {
+        let hr = self.object.hit_sampled(&self.local_ray(ray), min_t, max_t, sampler)?;
+        Some(self.world_hit(ray, hr))
+    }
+
+    fn crossings(&self, ray: &Ray, min_t: f64, max_t: f64) -> Vec<HitRecord<'_>> {
+        let crossings = self.object.crossings(&self.local_ray(ray), min_t, max_t);
+        crossings.into_iter().map(|hr| self.world_hit(ray, hr)).collect()
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.object.bounding_box().map(|bbox| self.transform.bounding_box(&bbox))
+    }
+
+    fn is_light(&self) -> bool {
+        self.object.is_light()
+    }
+
+    fn sample_direction(&self, origin: Point, time: f64, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        let local_origin = self.transform.inverse().point(origin);
+        let dir = self.object.sample_direction(local_origin, time, sampler)?;
+        Some(self.transform.vector(dir))
+    }
+
+    // The object's solid angle density gets converted to an area density, scaled by
+    // how much the transform stretches the surface there, and converted back.
+    fn direction_pdf(&self, origin: Point, dir: Vec3, time: f64) -> f64 {
+        let local = self.local_ray(&Ray::with_time(origin, dir.unit(), time));
+        let local_pdf = self.object.direction_pdf(local.origin, local.dir.unit(), time);
+        if local_pdf <= 0.0 {
+            return 0.0;
+        }
+        let hr = match self.object.hit(&local, 0.0, f64::INFINITY) {
+            Some(hr) => hr,
+            None => return 0.0,
+        };
+        let to_local = hr.p - local.origin;
+        let local_area_pdf = local_pdf * Vec3::dot(&hr.geometric_normal, &to_local.unit()).abs() / to_local.len_sq();
+
+        let normal = self.transform.normal(hr.geometric_normal);
+        let area_scale = self.transform.determinant().abs() * normal.len();
+        let to_hit = self.transform.point(hr.p) - origin;
+        let cos = Vec3::dot(&normal.unit(), &to_hit.unit()).abs();
+        if cos <= 0.0 {
+            return 0.0;
+        }
+        local_area_pdf / area_scale * to_hit.len_sq() / cos
+    }
+}
+
+#[test]
+fn test_transform() {
+    let t = Transform::scale(Vec3::new(2.0, 1.0, 0.5))
+        .then(&Transform::rotate_y(90.0))
+        .then(&Transform::translate(Vec3::new(1.0, 2.0, 3.0)));
+    let p = Point::new(1.0, 1.0, 1.0);
+    let moved = t.point(p);
+    assert!((moved - Point::new(1.5, 3.0, 1.0)).len() < 1e-12);
+    assert!((t.inverse().point(moved) - p).len() < 1e-12);
+    assert!((t.determinant() - 1.0).abs() < 1e-12);
+
+    // normals stay perpendicular to transformed tangents
+    let skew = Transform::scale(Vec3::new(3.0, 1.0, 1.0)).then(&Transform::rotate(Vec3::new(1.0, 1.0, 0.0), 30.0));
+    let (n, tangent) = (Vec3::new(1.0, 1.0, 0.0), Vec3::new(1.0, -1.0, 0.0));
+    assert!(Vec3::dot(&skew.normal(n), &skew.vector(tangent)).abs() < 1e-12);
+}
+
+#[test]
+fn test_instance_light_pdf() {
+    use crate::{Sphere, DiffuseLight, Color, SamplerKind, sample_sphere};
+
+    // a stretched and rotated spherical light seen from the origin
+    let light = Arc::new(Sphere::new(Point::new(0.0, 0.0, 0.0), 1.0, Arc::new(DiffuseLight::new(Color::new(1.0, 1.0, 1.0)))));
+    let transform = Transform::scale(Vec3::new(2.0, 0.5, 1.0))
+        .then(&Transform::rotate_z(30.0))
+        .then(&Transform::translate(Vec3::new(0.0, 0.0, -4.0)));
+    let instance = Instance::new(light, transform);
+    let origin = Point::new(0.0, 0.0, 0.0);
+    let hr = instance.hit(&Ray::new(origin, Vec3::new(0.0, 0.0, -1.0)), 0.0, f64::INFINITY).unwrap();
+    assert!((hr.t - 3.0).abs() < 1e-9);
+    assert!((hr.normal - Vec3::new(0.0, 0.0, 1.0)).len() < 1e-9);
+
+    // the solid angle found by uniform directions matches the average of 1 / pdf
+    let n = 20000;
+    let mut hits = 0;
+    let mut inv_pdf_sum = 0.0;
+    for i in 0..n {
+        let mut sampler = SamplerKind::Sobol.start(1, 0, i, n);
+        let dir = sample_sphere(sampler.next_2d());
+        if instance.hit(&Ray::new(origin, dir), 0.0, f64::INFINITY).is_some() {
+            hits += 1;
+        }
+        let dir = instance.sample_direction(origin, 0.0, &mut *sampler).unwrap();
+        inv_pdf_sum += 1.0 / instance.direction_pdf(origin, dir, 0.0);
+    }
+    let solid_angle = 4.0 * std::f64::consts::PI * hits as f64 / n as f64;
+    let estimate = inv_pdf_sum / n as f64;
+    assert!((estimate / solid_angle - 1.0).abs() < 0.02, "{} vs {}", estimate, solid_angle);
+}
@@ -0,0 +1,291 @@
+use crate::{Ray, Point, Vec3, Hittable, HitRecord, Material, Aabb, BvhNode, Sampler};
+use std::sync::Arc;
+
+const EPSILON: f64 = 1e-9;
+// flat triangles get a thin box so the slab test never sees a zero-width axis
+const BOX_PADDING: f64 = 1e-4;
+
+pub struct Triangle {
+    vertices: [Point; 3],
+    normals: Option<[Vec3; 3]>,
+    uvs: Option<[(f64, f64); 3]>,
+    material: Arc<dyn Material>,
+}
+
+impl Triangle {
+    pub fn new(a: Point, b: Point, c: Point, material: Arc<dyn Material>) -> Triangle {
+        Triangle {vertices: [a, b, c], normals: None, uvs: None, material}
+    }
+
+    pub fn normals(mut self, normals: [Vec3; 3]) -> Self {
+        self.normals = Some(normals);
+        self
+    }
+
+    pub fn uvs(mut self, uvs: [(f64, f64); 3]) -> Self {
+        self.uvs = Some(uvs);
+        self
+    }
+
+    pub fn hit_at(&self, ray: &Ray, min_t: f64, max_t: f64) -> Option<HitRecord<'_>> {
+        let hit = triangle_hit(ray, &self.vertices, self.normals.as_ref(), self.uvs.as_ref(), min_t, max_t)?;
+        Some(hit.record(ray, &*self.material, self))
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray, min_t: f64, max_t: f64) -> Option<HitRecord<'_>> {
+        self.hit_at(ray, min_t, max_t)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(triangle_box(&self.vertices))
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_light()
+    }
+
+    fn sample_direction(&self, origin: Point, _time: f64, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        Some(sample_triangle(&self.vertices, sampler) - origin)
+    }
+
+    fn direction_pdf(&self, origin: Point, dir: Vec3, _time: f64) -> f64 {
+        area_pdf(self.hit_at(&Ray::new(origin, dir.unit()), 0.0, f64::INFINITY), triangle_area(&self.vertices))
+    }
+}
+
+fn triangle_area(v: &[Point; 3]) -> f64 {
+    0.5 * Vec3::cross(&(v[1] - v[0]), &(v[2] - v[0])).len()
+}
+
+// uniformly distributed point on the triangle
+fn sample_triangle(v: &[Point; 3], sampler: &mut dyn Sampler) -> Point {
+    let (s, t) = sampler.next_2d();
+    let su = s.sqrt();
+    let (b1, b2) = (1.0 - su, t * su);
+    v[0] + b1 * (v[1] - v[0]) + b2 * (v[2] - v[0])
+}
+
+// converts a uniform density over area to solid angle, for a hit along a unit direction
+pub(crate) fn area_pdf(hit: Option<HitRecord>, area: f64) -> f64 {
+    match hit {
+        Some(hr) => {
+            let cos = Vec3::dot(&hr.geometric_normal, &hr.ray_dir).abs();
+            if cos <= 0.0 { 0.0 } else { hr.t * hr.t / (cos * area) }
+        },
+        None => 0.0,
+    }
+}
+
+struct TriangleHit {
+    t: f64,
+    outward_normal: Vec3,
+    geometric_normal: Vec3,
+    uv: (f64, f64),
+}
+
+impl TriangleHit {
+    fn record<'a>(&self, ray: &Ray, material: &'a dyn Material, object: &'a dyn Hittable) -> HitRecord<'a> {
+        HitRecord::new(ray, self.t, self.outward_normal, self.uv, material, object).with_geometric_normal(self.geometric_normal)
+    }
+}
+
+// Möller–Trumbore, returns the hit with barycentric-interpolated normal and uv
+fn triangle_hit(
+    ray: &Ray,
+    v: &[Point; 3],
+    normals: Option<&[Vec3; 3]>,
+    uvs: Option<&[(f64, f64); 3]>,
+    min_t: f64,
+    max_t: f64,
+) -> Option<TriangleHit> {
+    let edge1 = v[1] - v[0];
+    let edge2 = v[2] - v[0];
+    let pvec = Vec3::cross(&ray.dir, &edge2);
+    let det = Vec3::dot(&edge1, &pvec);
+    if det.abs() < EPSILON {
+        // ray parallel to the triangle
+        return None;
+    }
+    let inv_det = 1.0 / det;
+
+    let tvec = ray.origin - v[0];
+    let b1 = Vec3::dot(&tvec, &pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+    let qvec = Vec3::cross(&tvec, &edge1);
+    let b2 = Vec3::dot(&ray.dir, &qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+    let t = Vec3::dot(&edge2, &qvec) * inv_det;
+    if t < min_t || t > max_t {
+        return None;
+    }
+
+    let b0 = 1.0 - b1 - b2;
+    let geometric_normal = Vec3::cross(&edge1, &edge2).unit();
+    let outward_normal = match normals {
+        Some(n) => {
+            let shading = (b0 * n[0] + b1 * n[1] + b2 * n[2]).unit();
+            // keep the shading normal on the same side as the winding order
+            if Vec3::dot(&shading, &geometric_normal) < 0.0 { -1.0 * shading } else { shading }
+        },
+        None => geometric_normal,
+    };
+    let uv = match uvs {
+        Some(uv) => (
+            b0 * uv[0].0 + b1 * uv[1].0 + b2 * uv[2].0,
+            b0 * uv[0].1 + b1 * uv[1].1 + b2 * uv[2].1,
+        ),
+        None => (b1, b2),
+    };
+    Some(TriangleHit {t, outward_normal, geometric_normal, uv})
+}
+
+fn triangle_box(v: &[Point; 3]) -> Aabb {
+    let pad = Vec3::new(BOX_PADDING, BOX_PADDING, BOX_PADDING);
+    Aabb::new(
+        Vec3::min(&Vec3::min(&v[0], &v[1]), &v[2]) - pad,
+        Vec3::max(&Vec3::max(&v[0], &v[1]), &v[2]) + pad,
+    )
+}
+
+struct MeshData {
+    vertices: Vec<Point>,
+    normals: Option<Vec<Vec3>>,
+    uvs: Option<Vec<(f64, f64)>>,
+    material: Arc<dyn Material>,
+    faces: Vec<[usize; 3]>,
+    // running total of the face areas, for picking faces proportionally to their size
+    area_cdf: Vec<f64>,
+}
+
+impl MeshData {
+    fn face(&self, [a, b, c]: [usize; 3]) -> [Point; 3] {
+        [self.vertices[a], self.vertices[b], self.vertices[c]]
+    }
+
+    fn area(&self) -> f64 {
+        self.area_cdf.last().copied().unwrap_or(0.0)
+    }
+}
+
+struct MeshTriangle {
+    mesh: Arc<MeshData>,
+    indices: [usize; 3],
+}
+
+impl MeshTriangle {
+    fn vertices(&self) -> [Point; 3] {
+        self.mesh.face(self.indices)
+    }
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(&self, ray: &Ray, min_t: f64, max_t: f64) -> Option<HitRecord<'_>> {
+        let [a, b, c] = self.indices;
+        let normals = self.mesh.normals.as_ref().map(|n| [n[a], n[b], n[c]]);
+        let uvs = self.mesh.uvs.as_ref().map(|uv| [uv[a], uv[b], uv[c]]);
+        let hit = triangle_hit(ray, &self.vertices(), normals.as_ref(), uvs.as_ref(), min_t, max_t)?;
+        Some(hit.record(ray, &*self.mesh.material, self))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(triangle_box(&self.vertices()))
+    }
+
+    // density of sampling the whole mesh, which is what lights are registered as
+    fn direction_pdf(&self, origin: Point, dir: Vec3, _time: f64) -> f64 {
+        area_pdf(self.hit(&Ray::new(origin, dir.unit()), 0.0, f64::INFINITY), self.mesh.area())
+    }
+}
+
+// Indexed mesh: normals and uvs, when given, are indexed the same way as the vertices.
+pub struct TriangleMesh {
+    mesh: Arc<MeshData>,
+    bvh: Option<BvhNode>,
+}
+
+impl TriangleMesh {
+    /// # Panics
+    ///
+    /// If a face indexes past the vertices, or `normals` or `uvs` don't have one entry per vertex.
+    pub fn new(
+        vertices: Vec<Point>,
+        normals: Option<Vec<Vec3>>,
+        uvs: Option<Vec<(f64, f64)>>,
+        faces: Vec<[usize; 3]>,
+        material: Arc<dyn Material>,
+    ) -> TriangleMesh {
+        let n = vertices.len();
+        assert!(faces.iter().all(|f| f.iter().all(|&i| i < n)), "mesh face index out of range");
+        assert!(normals.as_ref().is_none_or(|v| v.len() == n), "mesh needs one normal per vertex");
+        assert!(uvs.as_ref().is_none_or(|v| v.len() == n), "mesh needs one uv per vertex");
+
+        let area_cdf = faces
+            .iter()
+            .scan(0.0, |total, &[a, b, c]| {
+                *total += triangle_area(&[vertices[a], vertices[b], vertices[c]]);
+                Some(*total)
+            })
+            .collect();
+        let mesh = Arc::new(MeshData {vertices, normals, uvs, material, faces, area_cdf});
+        let triangles: Vec<Arc<dyn Hittable>> = mesh
+            .faces
+            .iter()
+            .map(|&indices| Arc::new(MeshTriangle {mesh: mesh.clone(), indices}) as Arc<dyn Hittable>)
+            .collect();
+        let bvh = if triangles.is_empty() { None } else { Some(BvhNode::new(triangles)) };
+        TriangleMesh {mesh, bvh}
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, ray: &Ray, min_t: f64, max_t: f64) -> Option<HitRecord<'_>> {
+        self.bvh.as_ref().and_then(|bvh| bvh.hit(ray, min_t, max_t))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bvh.as_ref().and_then(|bvh| bvh.bounding_box())
+    }
+
+    fn is_light(&self) -> bool {
+        self.mesh.material.is_light()
+    }
+
+    fn sample_direction(&self, origin: Point, _time: f64, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        let area = self.mesh.area();
+        if area <= 0.0 {
+            return None;
+        }
+        let target = sampler.next_1d() * area;
+        let idx = self.mesh.area_cdf.partition_point(|&a| a < target).min(self.mesh.faces.len() - 1);
+        Some(sample_triangle(&self.mesh.face(self.mesh.faces[idx]), sampler) - origin)
+    }
+
+    fn direction_pdf(&self, origin: Point, dir: Vec3, _time: f64) -> f64 {
+        area_pdf(self.hit(&Ray::new(origin, dir.unit()), 0.0, f64::INFINITY), self.mesh.area())
+    }
+}
+
+#[test]
+fn test_triangle_hit() {
+    let tri = Triangle::new(
+        Point::new(0.0, 0.0, 0.0),
+        Point::new(1.0, 0.0, 0.0),
+        Point::new(0.0, 1.0, 0.0),
+        Arc::new(crate::Normal),
+    );
+    let ray = Ray::new(Point::new(0.25, 0.25, 1.0), Vec3::new(0.0, 0.0, -1.0));
+    let hr = tri.hit_at(&ray, 0.0, f64::INFINITY).unwrap();
+    assert!((hr.t - 1.0).abs() < 1e-12);
+    assert!(hr.front_face);
+    assert_eq!(hr.normal, Vec3::new(0.0, 0.0, 1.0));
+    assert!((hr.u - 0.25).abs() < 1e-12 && (hr.v - 0.25).abs() < 1e-12);
+
+    let miss = Ray::new(Point::new(0.75, 0.75, 1.0), Vec3::new(0.0, 0.0, -1.0));
+    assert!(tri.hit_at(&miss, 0.0, f64::INFINITY).is_none());
+}
@@ -93,6 +93,19 @@ impl DivAssign<f64> for Vec3 {
     }
 }
 
+impl Index<usize> for Vec3 {
+    type Output = f64;
+
+    fn index(&self, axis: usize) -> &f64 {
+        match axis {
+            0 => &self.0,
+            1 => &self.1,
+            2 => &self.2,
+            _ => panic!("Vec3 axis out of range: {}", axis),
+        }
+    }
+}
+
 impl Vec3 {
     pub fn len_sq(&self) -> f64 {
         self.0*self.0 + self.1*self.1 + self.2*self.2
@@ -114,42 +127,43 @@ impl Vec3 {
         )
     }
 
-    pub fn to_s(&self) -> String {
-        format!("{} {} {}", (self.0 * 255.999) as u8, (self.1 * 255.999) as u8, (self.2 * 255.999) as u8)
+    pub fn min(u: &Vec3, v: &Vec3) -> Vec3 {
+        Vec3::new(u.0.min(v.0), u.1.min(v.1), u.2.min(v.2))
     }
 
-    pub fn new(x: f64, y: f64, z: f64) -> Vec3 {
-        Vec3(x, y, z)
+    pub fn max(u: &Vec3, v: &Vec3) -> Vec3 {
+        Vec3::new(u.0.max(v.0), u.1.max(v.1), u.2.max(v.2))
     }
 
-    pub fn random(min: f64, max: f64) -> Vec3 {
-        let mut rng = rand::thread_rng();
-        Vec3::new(rng.gen_range(min, max), rng.gen_range(min, max), rng.gen_range(min, max))
+    pub const fn new(x: f64, y: f64, z: f64) -> Vec3 {
+        Vec3(x, y, z)
     }
 
-    pub fn random_in_unit() -> Vec3 {
-        loop {
-            let v = Vec3::random(-1.0, 1.0);
-            if v.len_sq() < 1.0 {
-                return v;
-            }
-        }
+    pub fn random<R: Rng + ?Sized>(rng: &mut R, min: f64, max: f64) -> Vec3 {
+        Vec3::new(rng.gen_range(min, max), rng.gen_range(min, max), rng.gen_range(min, max))
     }
 
-    pub fn random_unit() -> Vec3 {
-        let mut rng = rand::thread_rng();
+    pub fn random_unit<R: Rng + ?Sized>(rng: &mut R) -> Vec3 {
         let a = rng.gen_range(0.0f64, 2.0*PI);
         let z = rng.gen_range(-1.0f64, 1.0);
         let r = (1.0 - z*z).sqrt();
         Vec3::new(r*a.cos(), r*a.sin(), z)
     }
 
+    // two unit vectors completing an orthonormal basis with this (unit) vector
+    pub fn basis(&self) -> (Vec3, Vec3) {
+        let a = if self.0.abs() > 0.9 { Vec3::new(0.0, 1.0, 0.0) } else { Vec3::new(1.0, 0.0, 0.0) };
+        let v = Vec3::cross(self, &a).unit();
+        let u = Vec3::cross(self, &v);
+        (u, v)
+    }
+
     pub fn unit(&self) -> Vec3 {
         *self / self.len()
     }
 
     pub fn sqrt(&self) -> Vec3 {
-        return Vec3::new(self.0.sqrt(), self.1.sqrt(), self.2.sqrt())
+        Vec3::new(self.0.sqrt(), self.1.sqrt(), self.2.sqrt())
     }
 
     pub fn get_x(&self) -> f64 {self.0}